@@ -0,0 +1,68 @@
+use crate::config::Keys;
+
+/// What to do with the chosen line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Switch to chosen session, window or pane, resurrect dead session.
+    Switch,
+    /// Kill chosen session, window or pane.
+    Kill,
+    /// Rename chosen session or window, prompting for the new name with its
+    /// current one pre-filled.
+    Rename,
+    /// Create a new session named after the query string, nothing if it is
+    /// blank.
+    New,
+}
+
+impl Action {
+    /// Map key reported by `fzf --expect` to action.
+    pub fn from_key(key: &str, keys: &Keys) -> Action {
+        if key.is_empty() {
+            Action::Switch
+        } else if key == keys.kill {
+            Action::Kill
+        } else if key == keys.rename {
+            Action::Rename
+        } else if key == keys.new {
+            Action::New
+        } else {
+            Action::Switch
+        }
+    }
+}
+
+/// Result of a picker run.
+#[derive(Debug, Clone)]
+pub struct Choice {
+    pub action: Action,
+    /// Id of chosen line, empty if nothing matched.
+    pub id: String,
+    pub query: String,
+}
+
+impl Choice {
+    /// Parse output of `fzf --print-query --expect=...`.
+    pub fn from_fzf_output(output: &str, keys: &Keys) -> Option<Choice> {
+        let mut lines = output.lines();
+        let query = lines.next()?.to_string();
+        let key = lines.next().unwrap_or("");
+        let id = lines
+            .next()
+            .and_then(|line| line.split('\t').next())
+            .unwrap_or("")
+            .to_string();
+
+        let choice = Choice {
+            action: Action::from_key(key, keys),
+            id,
+            query,
+        };
+
+        if choice.id.is_empty() && choice.action != Action::New {
+            None
+        } else {
+            Some(choice)
+        }
+    }
+}
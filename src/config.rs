@@ -3,136 +3,561 @@ use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use clap::{crate_authors, crate_description, crate_name, crate_version, App, AppSettings};
-use serde::Deserialize;
+use clap::{crate_authors, crate_description, crate_name, crate_version, App, Arg, ArgMatches};
+use serde::{Deserialize, Deserializer};
 
+use crate::error::TavError;
+use crate::layout::PopupOptions;
 use crate::logging::*;
+use crate::tmux::server::Server;
 
-/// Return config dir string.
-/// Return config dir.
-pub fn dir() -> PathBuf {
-    let mut default_path = dirs::home_dir().unwrap();
-    default_path.push(".config/tav");
+mod frecency;
+mod fzf;
+mod icons;
+mod project;
+mod theme;
+
+pub use frecency::{FrecencyOptions, Source};
+pub use fzf::{FzfOptions, Keys};
+pub use icons::{Icons, Preset};
+pub use project::{ProjectRoot, Projects};
+pub use theme::{Colour, Theme};
 
+/// Return config dir, `$XDG_CONFIG_HOME/tav` or `~/.config/tav`.
+pub fn dir() -> PathBuf {
     match std::env::var("XDG_CONFIG_HOME") {
-        Ok(path) => Path::new(&path).join("ap"),
-        _ => default_path,
+        Ok(path) if !path.is_empty() => Path::new(&path).join("tav"),
+        _ => {
+            let mut path = dirs::home_dir().unwrap_or_default();
+            path.push(".config/tav");
+            path
+        }
+    }
+}
+
+/// Return config file path, `--config` option takes precedence over
+/// `$TAV_CONFIG`, then `tav.toml` in config dir.
+pub fn path(option: Option<&str>) -> PathBuf {
+    if let Some(path) = option {
+        return expand(path);
+    }
+
+    match std::env::var("TAV_CONFIG") {
+        Ok(path) if !path.is_empty() => expand(&path),
+        _ => dir().join("tav.toml"),
     }
 }
 
+/// Expand leading `~` and environment variables in `path`.
+pub fn expand(path: &str) -> PathBuf {
+    match shellexpand::full(path) {
+        Ok(path) => PathBuf::from(path.as_ref()),
+        Err(error) => {
+            warn!("failed to expand {:?}: {}", path, error);
+            PathBuf::from(path)
+        }
+    }
+}
+
+/// Accept either a single string or an array of strings.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct DeadSession {
-    pub dir: String,
     pub names: Vec<String>,
+    /// Session script (`*.tmux-session.zsh`) or definition (`*.toml`) of each
+    /// name.
+    pub paths: HashMap<String, PathBuf>,
 }
 
 impl DeadSession {
     pub fn max_name_width(&self) -> usize {
-        self.names.iter().map(|n| n.len()).max().unwrap_or(0)
+        self.names
+            .iter()
+            .map(|n| console::measure_text_width(n))
+            .max()
+            .unwrap_or(0)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Task {
-    List,
+    List {
+        format: ListFormat,
+    },
     Fzf,
+    Ui,
+    Save {
+        /// Session name or id, `None` for current session.
+        session: Option<String>,
+        /// Save as session definition (`*.toml`) instead of script.
+        toml: bool,
+        force: bool,
+    },
+    /// Print content of a window or pane.
+    Preview {
+        id: String,
+    },
+    #[default]
     Popup,
 }
 
-impl Default for Task {
-    fn default() -> Self {
-        Self::Popup
+/// How sessions and windows are ordered in the picker.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// Sessions by id, windows by index.
+    #[default]
+    Id,
+    /// Sessions and windows by name.
+    Name,
+    /// Most recently active first.
+    Activity,
+}
+
+impl Order {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::Activity => "activity",
+        }
+    }
+}
+
+impl std::str::FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(Self::Id),
+            "name" => Ok(Self::Name),
+            "activity" => Ok(Self::Activity),
+            _ => Err(format!("invalid order: {}", s)),
+        }
+    }
+}
+
+/// How tav talks to tmux.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Run `tmux` for each command.
+    #[default]
+    Process,
+    /// Keep a control mode (`tmux -C`) connection to each server.
+    Control,
+}
+
+/// Output format of `tav list`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ListFormat {
+    /// Colored lines fed to fzf.
+    #[default]
+    Feed,
+    Json,
+    /// One line per pane, with header line. Tabs, line breaks and backslashes
+    /// in fields are escaped as `\t`, `\n`, `\r` and `\\`.
+    Tsv,
+    /// Indented tree.
+    Plain,
+}
+
+impl std::str::FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "feed" => Ok(Self::Feed),
+            "json" => Ok(Self::Json),
+            "tsv" => Ok(Self::Tsv),
+            "plain" => Ok(Self::Plain),
+            _ => Err(format!("invalid list format: {}", s)),
+        }
     }
 }
 
 /// The all-in-one configuration model.
 #[derive(Clone, Deserialize, Debug, Default)]
 pub struct Config {
+    /// Session icons by exact name.
     #[serde(default)]
     pub session_icons: HashMap<String, String>,
 
     #[serde(default)]
-    pub sessions_dir: String,
+    pub icons: Icons,
+
+    /// Directories to discover dead sessions in, as written in config file.
+    #[serde(default, deserialize_with = "one_or_many")]
+    sessions_dir: Vec<String>,
+
+    /// Resolved `sessions_dir`, default to `sessions` in config dir.
+    #[serde(skip)]
+    pub sessions_dirs: Vec<PathBuf>,
+
+    /// Path of loaded config file.
+    #[serde(skip)]
+    pub path: PathBuf,
+
+    /// Where to discover projects, offered as sessions to create.
+    #[serde(default)]
+    pub projects: Vec<ProjectRoot>,
+
+    /// Directory jumper database to offer frequently used directories from,
+    /// as more projects.
+    #[serde(default)]
+    pub frecency: Option<FrecencyOptions>,
+
+    /// Extra tmux servers to list, by socket name (`-L`) or socket path
+    /// (`-S`), besides the default one.
+    #[serde(default)]
+    pub servers: Vec<Server>,
+
+    #[serde(default)]
+    pub backend: Backend,
+
+    /// List panes under each window.
+    #[serde(default)]
+    pub show_panes: bool,
+
+    /// Annotate windows and panes with git branch and status of their
+    /// current path.
+    #[serde(default)]
+    pub git_status: bool,
+
+    #[serde(default)]
+    pub order: Order,
+
+    #[serde(default)]
+    pub theme: Theme,
+
+    #[serde(default)]
+    pub fzf: FzfOptions,
+
+    #[serde(default)]
+    pub popup: PopupOptions,
+
+    #[serde(default)]
+    pub keys: Keys,
 
     #[serde(skip)]
     pub dead_session: DeadSession,
 
+    /// Discovered `projects`, then directories from `frecency`.
+    #[serde(skip)]
+    pub project: Projects,
+
     #[serde(skip)]
     pub task: Task,
 }
 
 impl Config {
     pub fn load() -> Config {
-        let mut cfg = Self::load_from_config().unwrap_or_else(|error| {
-            warn!("failed to load config: {:#?}", error,);
+        let matches = Self::app().get_matches();
+
+        let path = path(matches.value_of("config"));
+        let mut cfg = Self::load_from_config(&path).unwrap_or_else(|error| {
+            warn!("failed to load config {:?}: {:#?}", path, error);
             Config::default()
         });
+        cfg.path = path;
 
         debug!("load config: {:#?}", cfg);
 
-        cfg.parse_args();
+        cfg.apply_args(&matches);
+        cfg.resolve_sessions_dirs();
         cfg.discover_dead_sessions();
 
+        // fzf runs preview on every cursor move, spare it the directory walk
+        // unless it shows a project
+        let needs_projects = match &cfg.task {
+            Task::Preview { id } => id.starts_with("[project]"),
+            _ => true,
+        };
+        if needs_projects {
+            cfg.discover_projects();
+        }
+
         cfg
     }
 
-    fn load_from_config() -> Result<Config, Box<dyn Error>> {
-        let mut path = dir();
-        path.push("tav.toml");
+    /// Global options which reproduce this config in another tav process,
+    /// e.g. the one in popup, including those given on command line.
+    pub fn args(&self) -> crate::error::Result<Vec<String>> {
+        let path = self
+            .path
+            .to_str()
+            .ok_or_else(|| TavError::NotUtf8(self.path.clone()))?;
+        let mut args = vec!["--config".to_string(), path.to_string()];
 
-        let text = fs::read_to_string(&path);
+        for server in &self.servers {
+            args.extend(server.args().iter().map(|a| a.to_string()));
+        }
+        if self.backend == Backend::Control {
+            args.push("--control".to_string());
+        }
+        if self.show_panes {
+            args.push("--panes".to_string());
+        }
+        if self.git_status {
+            args.push("--git".to_string());
+        }
+        args.push("--order".to_string());
+        args.push(self.order.as_str().to_string());
+
+        Ok(args)
+    }
+
+    fn load_from_config(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let text = fs::read_to_string(path);
         Ok(toml::from_str(&text?)?)
     }
 
-    fn parse_args(&mut self) {
+    fn app() -> App<'static> {
         let list_cmd = App::new("list")
             .aliases(&["l", "ls"])
-            .about("Print out colored feed for `fzf`, for debug purpose")
-            .setting(AppSettings::Hidden);
+            .about("Print out tmux tree, dead sessions and projects, for scripts and status bars")
+            .arg(
+                Arg::new("format")
+                    .short('f')
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(["feed", "json", "tsv", "plain"])
+                    .default_value("feed")
+                    .help("Output format, `feed` is the colored input of fzf"),
+            );
 
         let fzf_cmd = App::new("fzf")
             .visible_alias("f")
-            .about("Run fzf directly to show tmux tree, not in tmux popup window")
-            .setting(AppSettings::Hidden);
+            .about("Run fzf directly to show tmux tree, not in tmux popup window");
 
-        let matches = App::new(crate_name!())
+        let ui_cmd = App::new("ui")
+            .visible_alias("u")
+            .about("Run the builtin picker to show tmux tree, no `fzf` required");
+
+        let save_cmd = App::new("save")
+            .about("Save a live session as a resurrectable session script or definition")
+            .arg(Arg::new("session").help("Session name or id, default to current session"))
+            .arg(
+                Arg::new("toml")
+                    .short('t')
+                    .long("toml")
+                    .help("Save as declarative session definition instead of zsh script"),
+            )
+            .arg(
+                Arg::new("force")
+                    .short('f')
+                    .long("force")
+                    .help("Overwrite existing session script"),
+            );
+
+        let preview_cmd = App::new("preview")
+            .about("Print content of a window or pane, used as fzf preview")
+            .arg(
+                Arg::new("id")
+                    .required(true)
+                    .help("Session, window or pane id, as in the first column of feed"),
+            );
+
+        App::new(crate_name!())
             .author(crate_authors!())
             .version(crate_version!())
             .about(crate_description!())
+            .arg(
+                Arg::new("config")
+                    .short('c')
+                    .long("config")
+                    .global(true)
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Config file, default to $TAV_CONFIG or $XDG_CONFIG_HOME/tav/tav.toml"),
+            )
+            .arg(
+                Arg::new("socket-name")
+                    .short('L')
+                    .long("socket-name")
+                    .global(true)
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .value_name("NAME")
+                    .help("List tmux server with socket name, as `tmux -L`, can be repeated"),
+            )
+            .arg(
+                Arg::new("socket-path")
+                    .short('S')
+                    .long("socket-path")
+                    .global(true)
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .value_name("PATH")
+                    .help("List tmux server with socket path, as `tmux -S`, can be repeated"),
+            )
+            .arg(
+                Arg::new("control")
+                    .long("control")
+                    .global(true)
+                    .help("Talk to tmux in control mode, over one connection per server"),
+            )
+            .arg(
+                Arg::new("panes")
+                    .short('p')
+                    .long("panes")
+                    .global(true)
+                    .help("List panes under each window"),
+            )
+            .arg(
+                Arg::new("git")
+                    .short('g')
+                    .long("git")
+                    .global(true)
+                    .help("Annotate windows and panes with git branch and status"),
+            )
+            .arg(
+                Arg::new("order")
+                    .short('o')
+                    .long("order")
+                    .global(true)
+                    .takes_value(true)
+                    .possible_values(["id", "name", "activity"])
+                    .help("Order sessions and windows by id, name or last activity"),
+            )
             .subcommand(list_cmd)
             .subcommand(fzf_cmd)
-            .get_matches();
+            .subcommand(ui_cmd)
+            .subcommand(save_cmd)
+            .subcommand(preview_cmd)
+    }
+
+    fn apply_args(&mut self, matches: &ArgMatches) {
+        // servers on command line replace those in config file
+        let names = matches.values_of("socket-name").into_iter().flatten();
+        let paths = matches.values_of("socket-path").into_iter().flatten();
+        let servers: Vec<Server> = names
+            .map(|n| Server::Name(n.to_string()))
+            .chain(paths.map(|p| Server::Path(expand(p).to_string_lossy().into_owned())))
+            .collect();
+        if !servers.is_empty() {
+            self.servers = servers;
+        }
 
-        self.task = if let Some(_matches) = matches.subcommand_matches("list") {
-            Task::List
+        if matches.is_present("control") {
+            self.backend = Backend::Control;
+        }
+
+        if matches.is_present("panes") {
+            self.show_panes = true;
+        }
+
+        if matches.is_present("git") {
+            self.git_status = true;
+        }
+
+        if let Some(order) = matches.value_of("order") {
+            self.order = order.parse().unwrap();
+        }
+
+        self.task = if let Some(matches) = matches.subcommand_matches("list") {
+            Task::List {
+                format: matches.value_of("format").unwrap().parse().unwrap(),
+            }
         } else if let Some(_matches) = matches.subcommand_matches("fzf") {
             Task::Fzf
+        } else if let Some(_matches) = matches.subcommand_matches("ui") {
+            Task::Ui
+        } else if let Some(matches) = matches.subcommand_matches("save") {
+            Task::Save {
+                session: matches.value_of("session").map(str::to_string),
+                toml: matches.is_present("toml"),
+                force: matches.is_present("force"),
+            }
+        } else if let Some(matches) = matches.subcommand_matches("preview") {
+            Task::Preview {
+                id: matches.value_of("id").unwrap().to_string(),
+            }
         } else {
             Task::Popup
         };
     }
 
+    /// Expand `sessions_dir` entries, relative paths are resolved against the
+    /// directory of config file.
+    fn resolve_sessions_dirs(&mut self) {
+        let base = self
+            .path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(dir);
+
+        self.sessions_dirs = if self.sessions_dir.is_empty() {
+            vec![dir().join("sessions")]
+        } else {
+            self.sessions_dir
+                .iter()
+                .map(|d| base.join(expand(d)))
+                .collect()
+        };
+
+        debug!("sessions dirs: {:?}", self.sessions_dirs);
+    }
+
+    /// Discover `projects`, then add directories from `frecency`.
+    fn discover_projects(&mut self) {
+        self.project = Projects::discover(&self.projects);
+        if let Some(frecency) = &self.frecency {
+            for dir in frecency.directories() {
+                self.project.add(dir);
+            }
+        }
+    }
+
+    /// Collect session scripts and definitions, for same name, earlier dir
+    /// takes precedence, and definition takes precedence over script.
     fn discover_dead_sessions(&mut self) {
-        let mut path = dirs::home_dir().unwrap();
-        path.push(".config/tav/sessions");
-
-        self.sessions_dir = path.to_str().unwrap().to_string();
-
-        path.push("*.tmux-session.zsh");
-        let path = path.to_str().unwrap();
-
-        debug!("glob on {}", &path);
-        match glob::glob(path) {
-            Ok(paths) => {
-                self.dead_session.names = paths
-                    .filter_map(|result| {
-                        let pathbuf = result.ok()?;
-                        let filename = pathbuf.file_stem()?.to_str()?;
-                        Some(filename.strip_suffix(".tmux-session")?.to_string())
-                    })
-                    .collect::<Vec<String>>();
+        for dir in &self.sessions_dirs {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(error) => {
+                    debug!("skip sessions dir {:?}: {}", dir, error);
+                    continue;
+                }
+            };
+
+            let mut found = HashMap::new();
+            for path in entries.filter_map(|e| Some(e.ok()?.path())) {
+                let filename = match path.file_name().and_then(|n| n.to_str()) {
+                    Some(filename) => filename.to_string(),
+                    None => continue,
+                };
+
+                if let Some(name) = filename.strip_suffix(".toml") {
+                    found.insert(name.to_string(), path);
+                } else if let Some(name) = filename.strip_suffix(".tmux-session.zsh") {
+                    found.entry(name.to_string()).or_insert(path);
+                }
+            }
+
+            for (name, path) in found {
+                self.dead_session.paths.entry(name).or_insert(path);
             }
-            Err(error) => error!("failed to glob dead sessions: {:?}", error),
         }
+
+        let mut names: Vec<String> = self.dead_session.paths.keys().cloned().collect();
+        names.sort();
+        self.dead_session.names = names;
     }
 }
@@ -0,0 +1,239 @@
+use std::convert::TryInto;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use super::expand;
+use crate::logging::*;
+
+const HOUR: u64 = 3600;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Directory jumper whose database is read.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    #[default]
+    Zoxide,
+    Autojump,
+    Fasd,
+}
+
+/// Frequently used directories offered as sessions to create, the
+/// `[frecency]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FrecencyOptions {
+    pub source: Source,
+    /// Number of top ranked directories to offer.
+    pub limit: usize,
+    /// Database file, default to where `source` keeps it.
+    pub path: Option<String>,
+}
+
+impl Default for FrecencyOptions {
+    fn default() -> Self {
+        FrecencyOptions {
+            source: Source::default(),
+            limit: 10,
+            path: None,
+        }
+    }
+}
+
+impl FrecencyOptions {
+    /// Existing directories in database, highest score first.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        let path = match &self.path {
+            Some(path) => expand(path),
+            None => self.default_path(),
+        };
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!(
+                    "failed to read {:?} database {:?}: {}",
+                    self.source, path, error
+                );
+                return Vec::new();
+            }
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut entries = match self.source {
+            Source::Zoxide => parse_zoxide(&data, now).unwrap_or_else(|| {
+                warn!("unsupported zoxide database {:?}", path);
+                Vec::new()
+            }),
+            Source::Autojump => parse_autojump(&String::from_utf8_lossy(&data)),
+            Source::Fasd => parse_fasd(&String::from_utf8_lossy(&data), now),
+        };
+
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+        entries
+            .into_iter()
+            .map(|(dir, _)| dir)
+            .filter(|dir| dir.is_dir())
+            .take(self.limit)
+            .collect()
+    }
+
+    fn default_path(&self) -> PathBuf {
+        let env = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let data_dir = || dirs::data_local_dir().unwrap_or_default();
+
+        match self.source {
+            Source::Zoxide => env("_ZO_DATA_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| data_dir().join("zoxide"))
+                .join("db.zo"),
+            Source::Autojump => data_dir().join("autojump/autojump.txt"),
+            Source::Fasd => env("_FASD_DATA")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".fasd")),
+        }
+    }
+}
+
+/// Parse zoxide database (version 3): version `u32`, count `u64`, then for
+/// each directory, path length `u64`, path, rank `f64` and last access time
+/// `u64`, all little endian. Scored as zoxide does, `None` if it is not of
+/// that format.
+fn parse_zoxide(data: &[u8], now: u64) -> Option<Vec<(PathBuf, f64)>> {
+    let mut rest = data;
+    let mut take = |n: usize| -> Option<&[u8]> {
+        if rest.len() < n {
+            return None;
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Some(head)
+    };
+
+    let version = u32::from_le_bytes(take(4)?.try_into().ok()?);
+    if version != 3 {
+        return None;
+    }
+
+    let count = u64::from_le_bytes(take(8)?.try_into().ok()?);
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let len = u64::from_le_bytes(take(8)?.try_into().ok()?);
+        let path = String::from_utf8_lossy(take(len as usize)?).into_owned();
+        let rank = f64::from_le_bytes(take(8)?.try_into().ok()?);
+        let last_accessed = u64::from_le_bytes(take(8)?.try_into().ok()?);
+
+        let age = now.saturating_sub(last_accessed);
+        let score = match age {
+            0..HOUR => rank * 4.0,
+            HOUR..DAY => rank * 2.0,
+            DAY..WEEK => rank * 0.5,
+            _ => rank * 0.25,
+        };
+        entries.push((PathBuf::from(path), score));
+    }
+
+    Some(entries)
+}
+
+/// Parse autojump database, lines of `weight<TAB>path`.
+fn parse_autojump(text: &str) -> Vec<(PathBuf, f64)> {
+    text.lines()
+        .filter_map(|line| {
+            let (weight, path) = line.split_once('\t')?;
+            Some((PathBuf::from(path), weight.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse fasd database, lines of `path|rank|time`, scored as fasd does.
+fn parse_fasd(text: &str, now: u64) -> Vec<(PathBuf, f64)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, '|');
+            let time: u64 = fields.next()?.parse().ok()?;
+            let rank: f64 = fields.next()?.parse().ok()?;
+            let path = fields.next()?;
+
+            let age = now.saturating_sub(time);
+            let score = match age {
+                0..HOUR => rank * 6.0,
+                HOUR..DAY => rank * 4.0,
+                DAY..WEEK => rank / 2.0,
+                _ => rank / 4.0,
+            };
+            Some((PathBuf::from(path), score))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 100_000_000;
+
+    fn paths(mut entries: Vec<(PathBuf, f64)>) -> Vec<(String, f64)> {
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+        entries
+            .into_iter()
+            .map(|(p, s)| (p.to_string_lossy().into_owned(), s))
+            .collect()
+    }
+
+    #[test]
+    fn zoxide() {
+        let mut data = Vec::new();
+        data.extend(3u32.to_le_bytes());
+        data.extend(2u64.to_le_bytes());
+        for (path, rank, last_accessed) in [
+            ("/code/tav", 10.0f64, NOW - 2 * DAY),
+            ("/tmp", 4.0, NOW - 60),
+        ] {
+            data.extend((path.len() as u64).to_le_bytes());
+            data.extend(path.as_bytes());
+            data.extend(rank.to_le_bytes());
+            data.extend(last_accessed.to_le_bytes());
+        }
+
+        let entries = parse_zoxide(&data, NOW).unwrap();
+        assert_eq!(
+            paths(entries),
+            [("/tmp".to_string(), 16.0), ("/code/tav".to_string(), 5.0)]
+        );
+
+        // truncated or other version
+        assert_eq!(parse_zoxide(&data[..data.len() - 1], NOW), None);
+        data[0] = 2;
+        assert_eq!(parse_zoxide(&data, NOW), None);
+    }
+
+    #[test]
+    fn autojump() {
+        let text = "12.5\t/code/tav\n3\t/tmp\ninvalid\n";
+        assert_eq!(
+            paths(parse_autojump(text)),
+            [("/code/tav".to_string(), 12.5), ("/tmp".to_string(), 3.0)]
+        );
+    }
+
+    #[test]
+    fn fasd() {
+        let text = format!(
+            "/code/a|b|8|{}\n/tmp|2|{}\nbroken\n",
+            NOW - 10,
+            NOW - 2 * WEEK
+        );
+        assert_eq!(
+            paths(parse_fasd(&text, NOW)),
+            [("/code/a|b".to_string(), 48.0), ("/tmp".to_string(), 0.5)]
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Deserialize;
+
+/// Options passed to `fzf`, the `[fzf]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FzfOptions {
+    /// `--margin`, as `TRBL`, `TB,RL`, `T,RL,B` or `T,R,B,L`.
+    pub margin: String,
+    /// `--layout`.
+    pub layout: String,
+    /// `--color`.
+    pub color: String,
+    /// Show content of highlighted window in preview window.
+    pub preview: bool,
+    /// `--preview-window`, e.g. `right:50%` or `down:20`.
+    pub preview_window: String,
+    /// `--bind`, one binding per item.
+    pub bind: Vec<String>,
+    /// Extra arguments appended to the command line.
+    pub args: Vec<String>,
+}
+
+impl Default for FzfOptions {
+    fn default() -> Self {
+        FzfOptions {
+            margin: "3,5,3,3".to_string(),
+            layout: "reverse".to_string(),
+            color: "bg:-1,bg+:-1".to_string(), // transparent background
+            preview: true,
+            preview_window: "right:50%".to_string(),
+            bind: vec![
+                "ctrl-j:page-down".to_string(),
+                "ctrl-k:page-up".to_string(),
+                "ctrl-f:page-down".to_string(),
+                "ctrl-b:page-up".to_string(),
+            ],
+            args: Vec::new(),
+        }
+    }
+}
+
+/// Action keys in fzf notation (`ctrl-x`, `alt-n`), the `[keys]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Keys {
+    pub kill: String,
+    pub rename: String,
+    pub new: String,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Keys {
+            kill: "ctrl-x".to_string(),
+            rename: "ctrl-r".to_string(),
+            new: "alt-n".to_string(),
+        }
+    }
+}
+
+impl Keys {
+    /// Value of `fzf --expect`, `enter` is left for `Action::Switch`.
+    pub fn expect(&self) -> String {
+        [&self.kill, &self.rename, &self.new]
+            .iter()
+            .map(|k| k.as_str())
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+}
@@ -0,0 +1,272 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use super::expand;
+
+/// Built-in icon set, matching screenshots in `asset/`.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Nerd Fonts glyphs.
+    Nerd,
+    Emoji,
+    /// Plain characters, for terminals without either.
+    Ascii,
+}
+
+/// Window icons of each preset, by `pane_current_command`, used with
+/// `commands = true`.
+const COMMANDS: [(&[&str], [&str; 3]); 9] = [
+    // commands                          nerd        emoji   ascii
+    (&["vim", "nvim", "vi"], ["\u{e62b}", "📝", "v"]),
+    (&["ssh", "mosh", "et"], ["\u{f0c2}", "🌐", "@"]),
+    (&["cargo", "rustc", "rustup"], ["\u{e7a8}", "🦀", "r"]),
+    (&["htop", "top", "btop", "btm"], ["\u{f080}", "📊", "%"]),
+    (&["git", "lazygit", "tig"], ["\u{f126}", "🌿", "g"]),
+    (&["python", "python3", "ipython"], ["\u{e73c}", "🐍", "p"]),
+    (&["node", "npm", "yarn", "deno"], ["\u{e718}", "📦", "n"]),
+    (&["man", "less", "bat"], ["\u{f02d}", "📖", "?"]),
+    (&["zsh", "bash", "fish", "sh"], ["\u{f120}", "🐚", "$"]),
+];
+
+impl Preset {
+    fn index(self) -> usize {
+        match self {
+            Preset::Nerd => 0,
+            Preset::Emoji => 1,
+            Preset::Ascii => 2,
+        }
+    }
+
+    fn window(self, command: &str) -> Option<&'static str> {
+        COMMANDS
+            .iter()
+            .find(|(commands, _)| commands.contains(&command))
+            .map(|(_, icons)| icons[self.index()])
+    }
+
+    fn dead_session(self) -> &'static str {
+        ["☒", "☒", "x"][self.index()]
+    }
+
+    fn project(self) -> &'static str {
+        ["\u{f07b}", "📁", "+"][self.index()]
+    }
+}
+
+/// Glob pattern, or regular expression if written as `/regex/`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Pattern {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            Some(regex) if !regex.is_empty() => Regex::new(regex)
+                .map(Pattern::Regex)
+                .map_err(|e| format!("invalid regex {:?}: {}", s, e)),
+            _ => glob::Pattern::new(&s)
+                .map(Pattern::Glob)
+                .map_err(|e| format!("invalid glob {:?}: {}", s, e)),
+        }
+    }
+}
+
+impl Pattern {
+    pub fn matches(&self, s: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob.matches(s),
+            Pattern::Regex(regex) => regex.is_match(s),
+        }
+    }
+
+    /// Like `matches`, with leading `~` and environment variables of glob
+    /// expanded, for matching paths.
+    fn matches_path(&self, path: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob::Pattern::new(&expand(glob.as_str()).to_string_lossy())
+                .map(|glob| glob.matches(path))
+                .unwrap_or(false),
+            Pattern::Regex(regex) => regex.is_match(path),
+        }
+    }
+}
+
+/// Icon of sessions whose name matches.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionRule {
+    pub name: Pattern,
+    pub icon: String,
+}
+
+/// Icon of windows whose active pane matches both `command` and `path`, if
+/// given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindowRule {
+    /// Matched against `pane_current_command`.
+    pub command: Option<Pattern>,
+    /// Matched against `pane_current_path`.
+    pub path: Option<Pattern>,
+    pub icon: String,
+}
+
+/// Icon of projects whose session name matches `name` and directory matches
+/// `path`, if given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectRule {
+    pub name: Option<Pattern>,
+    pub path: Option<Pattern>,
+    pub icon: String,
+}
+
+/// Session, window and project icons, the `[icons]` section.
+///
+/// Rules are tried in order, the first match wins, then the preset. Exact
+/// names in `session_icons` take precedence over session rules. Windows
+/// get icons of the preset only with `commands`, otherwise the theme
+/// `window_symbol`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Icons {
+    pub preset: Option<Preset>,
+    /// Window icons of common commands from the preset.
+    pub commands: bool,
+    pub sessions: Vec<SessionRule>,
+    pub windows: Vec<WindowRule>,
+    pub projects: Vec<ProjectRule>,
+}
+
+impl Icons {
+    pub fn session(&self, name: &str) -> Option<&str> {
+        self.sessions
+            .iter()
+            .find(|rule| rule.name.matches(name))
+            .map(|rule| rule.icon.as_str())
+    }
+
+    pub fn window(&self, command: &str, path: &str) -> Option<&str> {
+        let rule = self.windows.iter().find(|rule| {
+            (rule.command.is_some() || rule.path.is_some())
+                && rule.command.as_ref().is_none_or(|p| p.matches(command))
+                && rule.path.as_ref().is_none_or(|p| p.matches_path(path))
+        });
+
+        match rule {
+            Some(rule) => Some(&rule.icon),
+            None if self.commands => self.preset.and_then(|p| p.window(command)),
+            None => None,
+        }
+    }
+
+    /// Mark on the right of dead session lines.
+    pub fn dead_session(&self) -> Option<&str> {
+        self.preset.map(Preset::dead_session)
+    }
+
+    /// Mark on the right of line of project `name` in directory `path`.
+    pub fn project(&self, name: &str, path: &str) -> Option<&str> {
+        let rule = self.projects.iter().find(|rule| {
+            (rule.name.is_some() || rule.path.is_some())
+                && rule.name.as_ref().is_none_or(|p| p.matches(name))
+                && rule.path.as_ref().is_none_or(|p| p.matches_path(path))
+        });
+
+        match rule {
+            Some(rule) => Some(&rule.icon),
+            None => self.preset.map(Preset::project),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icons(text: &str) -> Icons {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn session_rules() {
+        let icons = icons(
+            r#"
+            sessions = [
+                { name = "dot*", icon = "D" },
+                { name = "/^(?i)neo/", icon = "N" },
+                { name = "*", icon = "*" },
+            ]
+            "#,
+        );
+
+        assert_eq!(icons.session("dotfiles"), Some("D"));
+        assert_eq!(icons.session("Neovim"), Some("N"));
+        assert_eq!(icons.session("tav"), Some("*"));
+        assert_eq!(Icons::default().session("tav"), None);
+    }
+
+    #[test]
+    fn window_rules() {
+        std::env::set_var("TAV_TEST_CODE", "/code");
+        let icons = icons(
+            r#"
+            preset = "emoji"
+            windows = [
+                { command = "/^n?vim$/", path = "$TAV_TEST_CODE/tav*", icon = "T" },
+                { command = "/^n?vim$/", icon = "V" },
+                { path = "/tmp/*", icon = "t" },
+            ]
+            "#,
+        );
+
+        assert_eq!(icons.window("nvim", "/code/tav/src"), Some("T"));
+        assert_eq!(icons.window("vim", "/home"), Some("V"));
+        assert_eq!(icons.window("zsh", "/tmp/x"), Some("t"));
+        // command icons of preset are opt-in
+        assert_eq!(icons.window("cargo", "/home"), None);
+        assert_eq!(icons.dead_session(), Some("☒"));
+        assert_eq!(Icons::default().dead_session(), None);
+
+        let icons = Icons {
+            commands: true,
+            ..icons
+        };
+        assert_eq!(icons.window("cargo", "/home"), Some("🦀"));
+        assert_eq!(icons.window("unknown", "/home"), None);
+    }
+
+    #[test]
+    fn project_rules() {
+        let icons = icons(
+            r#"
+            projects = [
+                { name = "dot*", icon = "D" },
+                { path = "/work/**", icon = "W" },
+            ]
+            "#,
+        );
+
+        assert_eq!(icons.project("dotfiles", "/home/dotfiles"), Some("D"));
+        assert_eq!(icons.project("api", "/work/team/api"), Some("W"));
+        assert_eq!(icons.project("tav", "/code/tav"), None);
+
+        let icons = Icons {
+            preset: Some(Preset::Ascii),
+            ..icons
+        };
+        assert_eq!(icons.project("tav", "/code/tav"), Some("+"));
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let result: Result<Icons, _> =
+            toml::from_str(r#"sessions = [{ name = "/(/", icon = "x" }]"#);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer};
+
+use super::expand;
+use crate::logging::*;
+
+/// Where to look for projects, an item of `projects`, either a plain path or
+/// a table.
+///
+/// ```toml
+/// projects = ["~/code/*", { path = "~/work", depth = 2, marker = ".git" }]
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectRoot {
+    /// Directory, or glob pattern of directories, e.g. `~/code/*`.
+    pub path: String,
+    /// Levels of subdirectories to search below `path`, `0` for matched
+    /// directories themselves.
+    pub depth: usize,
+    /// File or directory a project contains, e.g. `.git`, any directory is a
+    /// project if `None`.
+    pub marker: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for ProjectRoot {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Path(String),
+            Table {
+                path: String,
+                #[serde(default)]
+                depth: usize,
+                #[serde(default)]
+                marker: Option<String>,
+            },
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Path(path) => ProjectRoot {
+                path,
+                depth: 0,
+                marker: None,
+            },
+            Raw::Table {
+                path,
+                depth,
+                marker,
+            } => ProjectRoot {
+                path,
+                depth,
+                marker,
+            },
+        })
+    }
+}
+
+impl ProjectRoot {
+    /// Project directories under this root, sorted.
+    pub fn discover(&self) -> Vec<PathBuf> {
+        let pattern = expand(&self.path);
+        let matched = match glob::glob(&pattern.to_string_lossy()) {
+            Ok(paths) => paths.filter_map(|p| p.ok()).collect(),
+            Err(error) => {
+                warn!("invalid project root {:?}: {}", self.path, error);
+                Vec::new()
+            }
+        };
+
+        let mut found = Vec::new();
+        for dir in matched {
+            self.search(&dir, self.depth, &mut found);
+        }
+        found.sort();
+        found
+    }
+
+    /// Collect `dir` if it is a project, otherwise its subdirectories down to
+    /// `depth` levels, hidden ones excluded.
+    fn search(&self, dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+        if !dir.is_dir() {
+            return;
+        }
+
+        match &self.marker {
+            None if depth == 0 => return found.push(dir.to_path_buf()),
+            Some(marker) if dir.join(marker).exists() => return found.push(dir.to_path_buf()),
+            _ if depth == 0 => return,
+            _ => {}
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                debug!("skip project dir {:?}: {}", dir, error);
+                return;
+            }
+        };
+
+        for path in entries.filter_map(|e| Some(e.ok()?.path())) {
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if !hidden {
+                self.search(&path, depth - 1, found);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Projects {
+    pub names: Vec<String>,
+    /// Directory of each name.
+    pub paths: HashMap<String, PathBuf>,
+}
+
+impl Projects {
+    /// Discover projects under `roots`, for same name, earlier root takes
+    /// precedence.
+    pub fn discover(roots: &[ProjectRoot]) -> Projects {
+        let mut projects = Projects::default();
+
+        for root in roots {
+            for dir in root.discover() {
+                projects.add(dir);
+            }
+        }
+
+        debug!("projects: {:?}", projects.names);
+        projects
+    }
+
+    /// Add `dir` named after it, unless the name is taken.
+    pub fn add(&mut self, dir: PathBuf) {
+        let name = match dir.file_name() {
+            Some(name) => session_name(&name.to_string_lossy()),
+            None => return,
+        };
+        if !self.paths.contains_key(&name) {
+            self.names.push(name.clone());
+            self.paths.insert(name, dir);
+        }
+    }
+
+    pub fn max_name_width(&self) -> usize {
+        self.names
+            .iter()
+            .map(|n| console::measure_text_width(n))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Session name for directory `name`, tmux does not allow `.` and `:` in it.
+fn session_name(name: &str) -> String {
+    name.replace(['.', ':'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    fn mkdirs(base: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            fs::create_dir_all(base.join(dir)).unwrap();
+        }
+    }
+
+    fn names(found: &[PathBuf], base: &Path) -> Vec<String> {
+        found
+            .iter()
+            .map(|p| p.strip_prefix(base).unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn parse_roots() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            projects: Vec<ProjectRoot>,
+        }
+
+        let text = r#"projects = ["~/code/*", { path = "~/work", depth = 2, marker = ".git" }]"#;
+        let wrapper: Wrapper = toml::from_str(text).unwrap();
+        assert_eq!(
+            wrapper.projects,
+            [
+                ProjectRoot {
+                    path: "~/code/*".to_string(),
+                    depth: 0,
+                    marker: None,
+                },
+                ProjectRoot {
+                    path: "~/work".to_string(),
+                    depth: 2,
+                    marker: Some(".git".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn discover() {
+        let base = &temp_dir("discover");
+        mkdirs(
+            base,
+            &[
+                "code/tav/.git",
+                "code/tav/sub/.git",
+                "code/notes",
+                "work/acme/api/.git",
+                "work/acme/web/.git",
+                "work/acme/.hidden/.git",
+                "work/deep/a/b/.git",
+            ],
+        );
+        fs::write(base.join("code/file"), "").unwrap();
+
+        let root = |path: &str, depth, marker: Option<&str>| ProjectRoot {
+            path: base.join(path).to_string_lossy().into_owned(),
+            depth,
+            marker: marker.map(str::to_string),
+        };
+
+        let found = root("code/*", 0, None).discover();
+        assert_eq!(names(&found, base), ["code/notes", "code/tav"]);
+
+        let found = root("code/*", 0, Some(".git")).discover();
+        assert_eq!(names(&found, base), ["code/tav"]);
+
+        // nested projects are not searched
+        let found = root("code", 2, Some(".git")).discover();
+        assert_eq!(names(&found, base), ["code/tav"]);
+
+        let found = root("work", 2, Some(".git")).discover();
+        assert_eq!(names(&found, base), ["work/acme/api", "work/acme/web"]);
+
+        let found = root("work", 1, None).discover();
+        assert_eq!(names(&found, base), ["work/acme", "work/deep"]);
+    }
+
+    #[test]
+    fn names_of_projects() {
+        let base = &temp_dir("project-names");
+        mkdirs(base, &["a/tav.rs", "a/app", "b/app", "b/lib"]);
+
+        let root = |path: &str| ProjectRoot {
+            path: base.join(path).to_string_lossy().into_owned(),
+            depth: 0,
+            marker: None,
+        };
+
+        let projects = Projects::discover(&[root("a/*"), root("b/*")]);
+        assert_eq!(projects.names, ["app", "tav_rs", "lib"]);
+        assert_eq!(projects.paths["app"], base.join("a/app"));
+        assert_eq!(projects.max_name_width(), 6);
+    }
+}
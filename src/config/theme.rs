@@ -0,0 +1,94 @@
+use std::convert::TryFrom;
+
+use console::Color;
+use serde::Deserialize;
+
+/// Terminal color, written as a name (`magenta`) or a 256-color index (`242`).
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Colour(pub Color);
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let color = match s.as_str() {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            _ => Color::Color256(
+                s.parse()
+                    .map_err(|_| format!("invalid color: {:?}, expect a name or 0-255", s))?,
+            ),
+        };
+        Ok(Colour(color))
+    }
+}
+
+/// Colors and glyphs of picker lines, the `[theme]` section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub session: Colour,
+    pub window: Colour,
+    pub window_path: Colour,
+    pub pane: Colour,
+    pub pane_path: Colour,
+    pub dead_session: Colour,
+    pub project: Colour,
+    /// Branch and ahead/behind counts of git annotation.
+    pub git: Colour,
+    /// `*` of git annotation, for uncommitted changes.
+    pub git_dirty: Colour,
+    pub symbol: Colour,
+    pub current: Colour,
+
+    /// Session icon if not configured in `session_icons`.
+    pub session_symbol: String,
+    pub window_symbol: String,
+    pub pane_symbol: String,
+    /// Replaces window or pane symbol for the current one.
+    pub current_symbol: String,
+    /// Mark on the right of dead session lines without icon preset.
+    pub dead_session_symbol: String,
+    /// Mark on the right of project lines if not configured in `[icons]`.
+    pub project_symbol: String,
+
+    pub prompt: String,
+    pub pointer: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        const GRAY: Colour = Colour(Color::Color256(242));
+
+        Theme {
+            session: Colour(Color::Magenta),
+            window: Colour(Color::Green),
+            window_path: Colour(Color::Blue),
+            pane: Colour(Color::Cyan),
+            pane_path: GRAY,
+            dead_session: GRAY,
+            project: GRAY,
+            git: Colour(Color::Color256(109)),
+            git_dirty: Colour(Color::Red),
+            symbol: GRAY,
+            current: Colour(Color::Yellow),
+
+            session_symbol: "\u{a0}".to_string(),
+            window_symbol: "-".to_string(),
+            pane_symbol: "·".to_string(),
+            current_symbol: "▸".to_string(),
+            dead_session_symbol: String::new(),
+            project_symbol: "+".to_string(),
+
+            prompt: "▶ ".to_string(),
+            pointer: "▶".to_string(),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Errors surfaced to user instead of panicking.
+#[derive(Debug)]
+pub enum TavError {
+    /// Failed to spawn or talk to an external command.
+    Io { cmd: String, source: io::Error },
+
+    /// No tmux server to talk to.
+    NoServer,
+
+    /// Tmux command exited with failure.
+    Tmux { cmd: String, stderr: String },
+
+    /// Unexpected tmux output.
+    Parse { line: String, reason: String },
+
+    /// No such session in tmux.
+    SessionNotFound(String),
+
+    /// No directory for project.
+    ProjectNotFound(String),
+
+    /// Failed to read or write a file.
+    File { path: PathBuf, source: io::Error },
+
+    /// Invalid config value.
+    Config(String),
+
+    /// Invalid session definition file.
+    Definition { path: PathBuf, reason: String },
+
+    /// Session script exited with failure.
+    Script { path: PathBuf, stderr: String },
+
+    /// Path passed on to another command is not valid UTF-8.
+    NotUtf8(PathBuf),
+}
+
+pub type Result<T> = std::result::Result<T, TavError>;
+
+impl TavError {
+    pub fn io(cmd: &str, source: io::Error) -> TavError {
+        TavError::Io {
+            cmd: cmd.to_string(),
+            source,
+        }
+    }
+
+    pub fn file(path: &Path, source: io::Error) -> TavError {
+        TavError::File {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn parse(line: &str, reason: impl Into<String>) -> TavError {
+        TavError::Parse {
+            line: line.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for TavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TavError::Io { cmd, source } if source.kind() == io::ErrorKind::NotFound => {
+                write!(f, "`{}` not found, is it installed and in $PATH?", cmd)
+            }
+            TavError::Io { cmd, source } => write!(f, "failed to run `{}`: {}", cmd, source),
+            TavError::NoServer => write!(f, "tmux server is not running"),
+            TavError::Tmux { cmd, stderr } => write!(f, "`{}` failed: {}", cmd, stderr.trim()),
+            TavError::Parse { line, reason } => {
+                write!(f, "unexpected tmux output ({}): {:?}", reason, line)
+            }
+            TavError::SessionNotFound(name) => write!(f, "can not find session `{}`", name),
+            TavError::ProjectNotFound(name) => write!(f, "can not find project `{}`", name),
+            TavError::File { path, source } => write!(f, "{}: {}", path.display(), source),
+            TavError::Config(reason) => write!(f, "invalid config: {}", reason),
+            TavError::Definition { path, reason } => {
+                write!(
+                    f,
+                    "invalid session definition {}: {}",
+                    path.display(),
+                    reason
+                )
+            }
+            TavError::Script { path, stderr } => {
+                write!(
+                    f,
+                    "session script {} failed: {}",
+                    path.display(),
+                    stderr.trim()
+                )
+            }
+            TavError::NotUtf8(path) => write!(f, "path is not valid UTF-8: {:?}", path),
+        }
+    }
+}
+
+impl std::error::Error for TavError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TavError::Io { source, .. } | TavError::File { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -1,53 +1,83 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::cmp::Reverse;
 use std::rc::Rc;
 
-use console::{pad_str, style, Alignment::*, Color};
+use console::{measure_text_width, pad_str, style, Alignment, Alignment::*};
 use log::debug;
 
-use crate::config::Config;
-use crate::tmux::snapshot::{Session, Snapshot, Window};
+use crate::config::{Config, Order};
+use crate::git::{GitCache, GitStatus};
+use crate::tmux::snapshot::{id_number, Current, Geometry, Pane, Session, Snapshot, Window};
 
 const SS_WIDTH: usize = 4; // session symbol width
-const WS_WIDTH: usize = 2; // window symbol width
+const WS_WIDTH: usize = 2; // minimum window symbol width
+const PS_WIDTH: usize = 2; // pane symbol width
 const LEFT_MARGIN: usize = 2; // for each fzf list line
 const MIN_GAP: usize = 4;
 
 const MIN_WIDTH: usize = 50;
-
-const GRAY: Color = Color::Color256(242);
-// lazy_static! {
-// static ref GRAY: Color = Color::Color256(246);
-// }
+/// Names are truncated down to this width to fit in `max_width`.
+const MIN_NAME_WIDTH: usize = 8;
+const MIN_PART2_WIDTH: usize = 10;
 
 pub struct Formatter<'a> {
     config: &'a Config,
-    snapshot: &'a Snapshot,
+    /// Snapshots of all servers, the default server first.
+    snapshots: &'a [Snapshot],
+    /// Columns available for the feed, names are truncated to fit in.
+    max_width: Option<usize>,
 
+    /// Window symbol width, wide enough for window icons.
+    ws_width: usize,
     part1_width: usize,
     part2_width: usize,
+    /// Width of git annotation column, between gap and part 2, `0` if
+    /// disabled or nothing is in a git working tree.
+    git_width: usize,
     gap: String,
 
+    git: GitCache,
+
     pub width: usize,  // feed columns
     pub height: usize, // feed lines
 
     pub feed: Vec<String>,
+
+    /// Index of the current window line in `feed`.
+    pub current_line: Option<usize>,
 }
 
 impl<'a> Formatter<'a> {
-    pub fn new(snapshot: &'a Snapshot, config: &'a Config) -> Formatter<'a> {
+    pub fn new(snapshots: &'a [Snapshot], config: &'a Config) -> Formatter<'a> {
+        Formatter::with_max_width(snapshots, config, None)
+    }
+
+    /// Like `new`, but fit feed in `max_width` columns if possible.
+    pub fn with_max_width(
+        snapshots: &'a [Snapshot],
+        config: &'a Config,
+        max_width: Option<usize>,
+    ) -> Formatter<'a> {
         let mut f = Formatter {
             config,
-            snapshot,
+            snapshots,
+            max_width,
 
+            ws_width: WS_WIDTH,
             part1_width: 0,
             part2_width: 0,
+            git_width: 0,
 
             gap: String::new(),
 
+            git: GitCache::new(),
+
             width: 0,
             height: 0,
 
             feed: Vec::with_capacity(100),
+            current_line: None,
         };
 
         f.calculate_sizes();
@@ -59,150 +89,429 @@ impl<'a> Formatter<'a> {
     fn calculate_sizes(&mut self) {
         // calculate sizes
 
+        let theme = &self.config.theme;
+        let ws_width = self
+            .windows()
+            .iter()
+            .map(|w| self.window_symbol(&w.borrow()).to_string())
+            .chain(vec![
+                theme.window_symbol.clone(),
+                theme.current_symbol.clone(),
+            ])
+            .map(|s| measure_text_width(&s) + 1)
+            .fold(WS_WIDTH, usize::max);
+
+        let max = |f: fn(&Geometry) -> usize| {
+            self.snapshots
+                .iter()
+                .map(|s| f(&s.geometry))
+                .max()
+                .unwrap_or(0)
+        };
+        let session_name_max_width = max(|g| g.session_name_max_width);
+
+        let pane_title_width = if self.config.show_panes {
+            max(|g| g.pane_title_max_width) + ws_width + PS_WIDTH
+        } else {
+            0
+        };
+
         let widths = [
-            self.snapshot.geometry.session_name_max_width,
-            self.snapshot.geometry.window_name_max_width,
+            session_name_max_width,
+            max(|g| g.window_name_max_width) + ws_width,
+            pane_title_width,
             self.config.dead_session.max_name_width(),
+            self.config.project.max_name_width(),
         ];
-        let part1_width = widths.iter().max().unwrap_or(&0);
+        let mut part1_width = *widths.iter().max().unwrap_or(&0);
 
-        let mut part2_width = self.snapshot.geometry.session_name_max_width;
+        let mut part2_width = session_name_max_width;
         part2_width += 4; // for `:1` window index part
-        part2_width = part2_width.max(10);
+        if self.config.show_panes {
+            part2_width += 3; // for `.1` pane index part
+        }
+        part2_width = part2_width.max(MIN_PART2_WIDTH);
+
+        if self.config.git_status {
+            let max = self
+                .current_paths()
+                .iter()
+                .filter_map(|dir| self.git.status(dir))
+                .map(|status| measure_text_width(&status.summary()))
+                .max()
+                .unwrap_or(0);
+            self.git_width = if max > 0 { max + 1 } else { 0 };
+        }
+
+        // truncate names in part 1 first, then session names in part 2
+        if let Some(max_width) = self.max_width {
+            let fixed = LEFT_MARGIN + SS_WIDTH + self.git_width + MIN_GAP;
+            let mut excess = (fixed + part1_width + part2_width).saturating_sub(max_width);
 
-        let width_without_gap = LEFT_MARGIN + SS_WIDTH + part1_width + part2_width;
+            let min_part1_width = ws_width + PS_WIDTH + MIN_NAME_WIDTH;
+            let cut = excess.min(part1_width.saturating_sub(min_part1_width));
+            part1_width -= cut;
+            excess -= cut;
+
+            let cut = excess.min(part2_width.saturating_sub(MIN_PART2_WIDTH));
+            part2_width -= cut;
+        }
+
+        let width_without_gap = LEFT_MARGIN + SS_WIDTH + part1_width + self.git_width + part2_width;
         let width_with_gap = width_without_gap + MIN_GAP;
-        let width = width_with_gap.max(MIN_WIDTH);
+        let min_width = self.max_width.unwrap_or(MIN_WIDTH).min(MIN_WIDTH);
+        let width = width_with_gap.max(min_width);
         let gap_width = width - width_without_gap;
 
-        self.part1_width = *part1_width;
+        self.ws_width = ws_width;
+        self.part1_width = part1_width;
         self.part2_width = part2_width;
         self.gap = xspan(gap_width);
         self.width = width;
     }
 
+    /// Windows of all snapshots.
+    fn windows(&self) -> Vec<Rc<RefCell<Window>>> {
+        self.snapshots
+            .iter()
+            .flat_map(|snapshot| snapshot.sessions.values())
+            .flat_map(|session| {
+                session
+                    .borrow()
+                    .windows
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Current paths of windows, and of panes if they are listed.
+    fn current_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for window in self.windows() {
+            let window = window.borrow();
+            paths.push(window.current_path());
+            if self.config.show_panes {
+                for pane in window.panes.values() {
+                    paths.push(pane.borrow().current_path.clone());
+                }
+            }
+        }
+        paths
+    }
+
+    /// Icon of session `name`, by exact name, then by icon rules.
+    fn session_symbol(&self, name: &str) -> &str {
+        match self.config.session_icons.get(name) {
+            Some(icon) => icon,
+            None => self
+                .config
+                .icons
+                .session(name)
+                .unwrap_or(&self.config.theme.session_symbol),
+        }
+    }
+
+    /// Icon of `window` by command and path of its active pane.
+    fn window_symbol(&self, window: &Window) -> &str {
+        let command = window.current_command();
+        let path = window.current_path();
+        self.config
+            .icons
+            .window(&command, &path)
+            .unwrap_or(&self.config.theme.window_symbol)
+    }
+
     fn compose_feed(&mut self) {
-        // live sessions
+        // live sessions, grouped by server if there are more than one
+
+        for snapshot in self.snapshots {
+            if self.snapshots.len() > 1 {
+                if !self.feed.is_empty() {
+                    self.feed.push("[sep]\t".to_string());
+                }
+                self.feed.push(self.server_line(snapshot));
+            }
+            self.compose_server(snapshot);
+        }
+
+        // separator line
+
+        if !self.config.dead_session.names.is_empty() && !self.feed.is_empty() {
+            let sep = "[sep]\t".to_string();
+            self.feed.push(sep);
+        }
+
+        // dead sessions, they are resurrected on the default server
+
+        for name in &self.config.dead_session.names {
+            if !self.is_alive(name) {
+                self.feed.push(self.dead_session_line(name));
+            }
+        }
+
+        // projects without live or dead session of the same name
+
+        let projects: Vec<&String> = self
+            .config
+            .project
+            .names
+            .iter()
+            .filter(|name| !self.is_alive(name))
+            .filter(|name| !self.config.dead_session.names.contains(name))
+            .collect();
+
+        if !projects.is_empty() && !self.feed.is_empty() {
+            self.feed.push("[sep]\t".to_string());
+        }
+        for name in projects {
+            self.feed.push(self.project_line(name));
+        }
+
+        self.height = self.feed.len();
+    }
 
-        let mut sessions = self
-            .snapshot
+    /// Whether session `name` is live on the default server.
+    fn is_alive(&self, name: &str) -> bool {
+        self.snapshots[0]
+            .sessions
+            .values()
+            .any(|s| s.borrow().name == name)
+    }
+
+    fn compose_server(&mut self, snapshot: &Snapshot) {
+        let mut sessions = snapshot
             .sessions
             .values()
             .cloned()
             .collect::<Vec<Rc<RefCell<Session>>>>();
 
-        sessions.sort_by_key(|s| s.borrow().id.clone());
+        match self.config.order {
+            Order::Id => sessions.sort_by_key(|s| id_number(&s.borrow().id)),
+            Order::Name => sessions.sort_by_key(|s| s.borrow().name.clone()),
+            Order::Activity => sessions.sort_by_key(|s| {
+                let s = s.borrow();
+                (Reverse(s.activity), id_number(&s.id))
+            }),
+        }
 
         for (index, session) in sessions.into_iter().enumerate() {
             let session = session.borrow();
             if index > 0 {
                 self.feed.push("[sep]\t".to_string());
             }
-            self.feed.push(self.live_session_line(&session));
+            self.feed.push(self.live_session_line(snapshot, &session));
 
             let mut windows = session
                 .windows
                 .values()
                 .cloned()
                 .collect::<Vec<std::rc::Rc<std::cell::RefCell<Window>>>>();
-            windows.sort_by_key(|x| x.borrow().index);
+            match self.config.order {
+                Order::Id => windows.sort_by_key(|x| x.borrow().index),
+                Order::Name => windows.sort_by_key(|x| x.borrow().name.clone()),
+                Order::Activity => windows.sort_by_key(|x| {
+                    let x = x.borrow();
+                    (Reverse(x.activity), x.index)
+                }),
+            }
 
             for window in &windows {
                 let window = window.borrow();
-                self.feed.push(self.window_line(&window));
-            }
-        }
-
-        // separator line
-
-        if !self.config.dead_session.names.is_empty() {
-            let sep = "[sep]\t".to_string();
-            self.feed.push(sep);
-        }
-
-        // dead sessions
-
-        for name in &self.config.dead_session.names {
-            if self
-                .snapshot
-                .sessions
-                .values()
-                .find(|s| &s.borrow().name == name)
-                .is_none()
-            {
-                self.feed.push(self.dead_session_line(name));
+                if is_current(snapshot, |c| c.is_window(&window.id)) {
+                    self.current_line = Some(self.feed.len());
+                }
+                self.feed.push(self.window_line(snapshot, &window));
+
+                if !self.config.show_panes {
+                    continue;
+                }
+
+                let mut panes = window
+                    .panes
+                    .values()
+                    .cloned()
+                    .collect::<Vec<Rc<RefCell<Pane>>>>();
+                panes.sort_by_key(|x| x.borrow().index);
+
+                for pane in &panes {
+                    let pane = pane.borrow();
+                    self.feed
+                        .push(self.pane_line(snapshot, &session, &window, &pane));
+                }
             }
         }
+    }
 
-        self.height = self.feed.len();
+    /// Header line of a server group, not selectable.
+    fn server_line(&self, snapshot: &Snapshot) -> String {
+        let theme = &self.config.theme;
+        // socket file name, which is the `-L` name for sockets in default dir
+        let name = std::path::Path::new(&snapshot.socket_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| snapshot.server.to_string());
+        let label = format!("[{}]", name);
+        let label = style(label).fg(theme.symbol.0).bold().to_string();
+
+        format!("[sep]\t{label}", label = label)
     }
 
-    fn live_session_line(&self, session: &Session) -> String {
+    fn live_session_line(&self, snapshot: &Snapshot, session: &Session) -> String {
         // symbol
-        let nbsp = "\u{a0}".to_string(); // default symbol
-        let symbol = self
-            .config
-            .session_icons
-            .get(&session.name)
-            .unwrap_or(&nbsp);
+        let theme = &self.config.theme;
+        let symbol = self.session_symbol(&session.name);
 
         let symbol = pad_str(symbol, SS_WIDTH, Left, None);
 
         // name
-        let name = style(&session.name).magenta().to_string();
-        let name = pad_str(&name, self.part1_width, Left, None);
+        let mut name = style(&session.name).fg(theme.session.0);
+        if is_current(snapshot, |c| c.is_session(&session.id)) {
+            name = name.bold().underlined();
+        }
+        let name = fit(&name.to_string(), self.part1_width, Left);
 
         format!(
             "{id}\t{symbol}{name}",
-            id = session.id,
+            id = snapshot.server.qualify(&session.id),
             symbol = symbol,
             name = name,
         )
     }
 
-    fn window_line(&self, window: &Window) -> String {
+    fn window_line(&self, snapshot: &Snapshot, window: &Window) -> String {
         // margin
         let margin = xspan(SS_WIDTH);
         // symbol
-        let symbol = style("-").fg(GRAY).to_string();
-        let symbol = pad_str(&symbol, WS_WIDTH, Left, None);
+        let theme = &self.config.theme;
+        let symbol = if is_current(snapshot, |c| c.is_window(&window.id)) {
+            style(theme.current_symbol.as_str()).fg(theme.current.0)
+        } else {
+            style(self.window_symbol(window)).fg(theme.symbol.0)
+        };
+        let symbol = symbol.to_string();
+        let symbol = pad_str(&symbol, self.ws_width, Left, None);
         // name
-        let name = style(&window.name).green().to_string();
-        let name = pad_str(&name, self.part1_width - WS_WIDTH, Left, Some(""));
+        let name = style(&window.name).fg(theme.window.0).to_string();
+        let name = fit(&name, self.part1_width - self.ws_width, Left);
         // path
         let session_name = window
             .session
             .upgrade()
             .map(|s| s.borrow().name.clone())
             .unwrap_or("[W]".to_string());
-        let mut path = format!("{}:{}", session_name, window.index);
-        path = style(path).blue().to_string();
-        let path = pad_str(&path, self.part2_width, Right, Some(""));
+        let path = self.location(&session_name, &format!(":{}", window.index));
+        let path = style(path).fg(theme.window_path.0).to_string();
+        let path = pad_str(&path, self.part2_width, Right, None);
+        // git
+        let git = self.git_column(&window.current_path());
 
         format!(
-            "{id}\t{margin}{symbol}{name}{gap}{path}",
-            id = window.id,
+            "{id}\t{margin}{symbol}{name}{gap}{git}{path}",
+            id = snapshot.server.qualify(&window.id),
             margin = margin,
             symbol = symbol,
             name = name,
             gap = self.gap,
+            git = git,
+            path = path,
+        )
+    }
+
+    fn pane_line(
+        &self,
+        snapshot: &Snapshot,
+        session: &Session,
+        window: &Window,
+        pane: &Pane,
+    ) -> String {
+        // margin
+        let margin = xspan(SS_WIDTH + self.ws_width);
+        // symbol
+        let theme = &self.config.theme;
+        let symbol = if is_current(snapshot, |c| c.is_pane(&pane.id)) {
+            style(&theme.current_symbol).fg(theme.current.0)
+        } else {
+            style(&theme.pane_symbol).fg(theme.symbol.0)
+        };
+        let symbol = symbol.to_string();
+        let symbol = pad_str(&symbol, PS_WIDTH, Left, None);
+        // title
+        let title = style(&pane.title).fg(theme.pane.0).to_string();
+        let title = fit(&title, self.part1_width - self.ws_width - PS_WIDTH, Left);
+        // path
+        let index = format!(":{}.{}", window.index, pane.index);
+        let path = self.location(&session.name, &index);
+        let path = style(path).fg(theme.pane_path.0).to_string();
+        let path = pad_str(&path, self.part2_width, Right, None);
+        // git
+        let git = self.git_column(&pane.current_path);
+
+        format!(
+            "{id}\t{margin}{symbol}{title}{gap}{git}{path}",
+            id = snapshot.server.qualify(&pane.id),
+            margin = margin,
+            symbol = symbol,
+            title = title,
+            gap = self.gap,
+            git = git,
             path = path,
         )
     }
 
+    /// `session_name` followed by `index`, the session name truncated to fit
+    /// in part 2.
+    fn location(&self, session_name: &str, index: &str) -> String {
+        let width = self.part2_width.saturating_sub(measure_text_width(index));
+        format!("{}{}", truncate(session_name, width), index)
+    }
+
+    /// Git annotation of `dir` padded to git column, branch and counts in
+    /// one color, dirty mark in another.
+    fn git_column(&self, dir: &str) -> String {
+        if self.git_width == 0 {
+            return String::new();
+        }
+
+        let text = match self.git.status(dir) {
+            Some(status) => self.git_annotation(&status),
+            None => String::new(),
+        };
+        pad_str(&text, self.git_width, Left, None).to_string()
+    }
+
+    fn git_annotation(&self, status: &GitStatus) -> String {
+        let theme = &self.config.theme;
+        let summary = status.summary();
+        let (branch, rest) = summary.split_at(status.branch.len());
+
+        let mut s = style(branch).fg(theme.git.0).to_string();
+        let rest = match rest.strip_prefix('*') {
+            Some(rest) => {
+                s.push_str(&style("*").fg(theme.git_dirty.0).to_string());
+                rest
+            }
+            None => rest,
+        };
+        s.push_str(&style(rest).fg(theme.git.0).to_string());
+        s
+    }
+
     fn dead_session_line(&self, name: &str) -> String {
         // symbol
-        let nbsp = "\u{a0}".to_string(); // default symbol
-        let symbol = self.config.session_icons.get(name).unwrap_or(&nbsp);
+        let theme = &self.config.theme;
+        let symbol = self.session_symbol(name);
         let symbol = pad_str(symbol, SS_WIDTH, Left, None);
 
         // left
-        let left = style(name).fg(GRAY).to_string();
-        let left = pad_str(&left, self.part1_width, Left, None);
+        let left = style(name).fg(theme.dead_session.0).to_string();
+        let left = fit(&left, self.part1_width, Left);
 
         // right
-        let right = " ";
-        let right = style(right).fg(GRAY).to_string();
+        let icon = self.config.icons.dead_session();
+        let right = format!("{} ", icon.unwrap_or(&theme.dead_session_symbol));
+        let right = style(right).fg(theme.dead_session.0).to_string();
         let right = pad_str(&right, self.part2_width, Right, Some(""));
 
         let line = format!(
@@ -223,12 +532,59 @@ impl<'a> Formatter<'a> {
         );
 
         debug!("dead session line: {}", line);
-        debug!(
-            "dead session line width: {}",
-            console::measure_text_width(&t)
-        );
+        debug!("dead session line width: {}", measure_text_width(&t));
         line
     }
+
+    fn project_line(&self, name: &str) -> String {
+        // symbol
+        let theme = &self.config.theme;
+        let symbol = self.session_symbol(name);
+        let symbol = pad_str(symbol, SS_WIDTH, Left, None);
+
+        // left
+        let left = style(name).fg(theme.project.0).to_string();
+        let left = fit(&left, self.part1_width, Left);
+
+        // right
+        let path = self.config.project.paths.get(name);
+        let path = path.map(|p| p.to_string_lossy()).unwrap_or_default();
+        let icon = self.config.icons.project(name, &path);
+        let right = format!("{} ", icon.unwrap_or(&theme.project_symbol));
+        let right = style(right).fg(theme.project.0).to_string();
+        let right = pad_str(&right, self.part2_width, Right, Some(""));
+
+        format!(
+            "[project]{name}\t{symbol}{left}{gap}{right}",
+            name = name,
+            symbol = symbol,
+            left = left,
+            gap = self.gap,
+            right = right,
+        )
+    }
+}
+
+/// Test against current location of calling client.
+fn is_current(snapshot: &Snapshot, test: impl Fn(&Current) -> bool) -> bool {
+    snapshot.current.as_ref().is_some_and(test)
+}
+
+/// Pad `s` to `width` columns, truncate it with an ellipsis if it is wider.
+fn fit(s: &str, width: usize, align: Alignment) -> String {
+    // a wide character may not fit in the last columns, pad after truncating
+    pad_str(&truncate(s, width), width, align, None).into_owned()
+}
+
+/// Truncate `s` to `width` columns with an ellipsis, if it is wider.
+fn truncate(s: &str, width: usize) -> Cow<'_, str> {
+    if measure_text_width(s) <= width {
+        Cow::Borrowed(s)
+    } else if width == 0 {
+        Cow::Borrowed("")
+    } else {
+        console::truncate_str(s, width, "…")
+    }
 }
 
 /// Transparent fixed length span.
@@ -236,3 +592,295 @@ pub fn xspan(width: usize) -> String {
     let s = style(".").black().to_string();
     pad_str(&s, width, Left, None).to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::Preset;
+    use crate::testing::fake;
+    use crate::tmux::fake::Fake;
+    use crate::tmux::server::Server;
+    use crate::tmux::snapshot;
+
+    fn feed(tmux: &Fake, config: &Config) -> Vec<String> {
+        let snapshots = snapshot::create_all(tmux, &config.servers).unwrap();
+        Formatter::new(&snapshots, config).feed
+    }
+
+    fn ids(feed: &[String]) -> Vec<&str> {
+        feed.iter().map(|l| l.split('\t').next().unwrap()).collect()
+    }
+
+    #[test]
+    fn sessions_and_windows() {
+        let feed = feed(&fake(), &Config::default());
+        assert_eq!(ids(&feed), ["$0", "@1", "@4", "[sep]", "$6", "@7"]);
+
+        let text = console::strip_ansi_codes(&feed[2]);
+        assert!(text.contains("run"));
+        assert!(text.trim_end().ends_with("alpha:1"));
+    }
+
+    #[test]
+    fn panes() {
+        let mut config = Config::default();
+        config.show_panes = true;
+        let feed = feed(&fake(), &config);
+        assert_eq!(
+            ids(&feed),
+            ["$0", "@1", "%2", "%3", "@4", "%5", "[sep]", "$6", "@7", "%8"]
+        );
+
+        let text = console::strip_ansi_codes(&feed[3]);
+        assert!(text.contains("shell"));
+        assert!(text.trim_end().ends_with("alpha:0.1"));
+    }
+
+    #[test]
+    fn order() {
+        let tmux = fake();
+        tmux.add_pane(&Server::Default, "alpha", "aaa", "");
+
+        let feed = feed(&tmux, &Config::default());
+        assert_eq!(ids(&feed)[..4], ["$0", "@1", "@4", "@9"]);
+
+        let mut config = Config::default();
+        config.order = Order::Name;
+        let feed = self::feed(&tmux, &config);
+        assert_eq!(ids(&feed)[..4], ["$0", "@9", "@1", "@4"]);
+
+        // the later created, the more recently active in fake
+        let mut config = Config::default();
+        config.order = Order::Activity;
+        let feed = self::feed(&tmux, &config);
+        assert_eq!(ids(&feed), ["$0", "@9", "@4", "@1", "[sep]", "$6", "@7"]);
+    }
+
+    #[test]
+    fn current_line() {
+        let tmux = fake();
+        let config = Config::default();
+
+        let snapshots = snapshot::create_all(&tmux, &[]).unwrap();
+        assert_eq!(Formatter::new(&snapshots, &config).current_line, None);
+
+        tmux.set_current("%5");
+        let snapshots = snapshot::create_all(&tmux, &[]).unwrap();
+        let formatter = Formatter::new(&snapshots, &config);
+
+        let line = formatter.current_line.unwrap();
+        assert!(formatter.feed[line].starts_with("@4\t"));
+        assert!(formatter.feed[line].contains(&config.theme.current_symbol));
+    }
+
+    #[test]
+    fn dead_sessions() {
+        let mut config = Config::default();
+        config.dead_session.names = vec!["alpha".to_string(), "zeta".to_string()];
+
+        let feed = feed(&fake(), &config);
+        assert_eq!(ids(&feed)[6..], ["[sep]", "[dead]zeta"]);
+
+        // no separator above dead sessions if nothing is alive
+        let feed = self::feed(&Fake::new(), &config);
+        assert_eq!(ids(&feed), ["[dead]alpha", "[dead]zeta"]);
+    }
+
+    #[test]
+    fn dead_session_marks() {
+        let mut config = Config::default();
+        config.dead_session.names = vec!["zeta".to_string()];
+        // right column, after the dot of gap
+        let right = |config: &Config| -> String {
+            let line = console::strip_ansi_codes(&feed(&Fake::new(), config)[0]).into_owned();
+            line.rsplit_once('.').unwrap().1.trim().to_string()
+        };
+
+        // blank without preset, no glyph a font may lack
+        assert_eq!(right(&config), "");
+
+        config.theme.dead_session_symbol = "z".to_string();
+        assert_eq!(right(&config), "z");
+
+        config.icons.preset = Some(Preset::Ascii);
+        assert_eq!(right(&config), "x");
+
+        // as in asset/nerd.png and asset/emoji.png
+        config.icons.preset = Some(Preset::Nerd);
+        assert_eq!(right(&config), "☒");
+    }
+
+    #[test]
+    fn projects() {
+        let mut config = Config::default();
+        config.dead_session.names = vec!["zeta".to_string()];
+        config.project.names = vec!["beta".to_string(), "tav".to_string(), "zeta".to_string()];
+
+        let feed = feed(&fake(), &config);
+        assert_eq!(
+            ids(&feed)[6..],
+            ["[sep]", "[dead]zeta", "[sep]", "[project]tav"]
+        );
+
+        config.dead_session.names.clear();
+        let feed = self::feed(&Fake::new(), &config);
+        assert_eq!(
+            ids(&feed),
+            ["[project]beta", "[project]tav", "[project]zeta"]
+        );
+    }
+
+    #[test]
+    fn project_icons() {
+        let mut config = Config::default();
+        config.project.add(PathBuf::from("/work/api"));
+        config.project.add(PathBuf::from("/code/tav"));
+        let right = |config: &Config| -> Vec<String> {
+            feed(&Fake::new(), config)
+                .iter()
+                .map(|l| console::strip_ansi_codes(l).trim_end().to_string())
+                .map(|l| l.chars().last().unwrap().to_string())
+                .collect()
+        };
+
+        // theme symbol without preset
+        assert_eq!(right(&config), ["+", "+"]);
+
+        config.icons = toml::from_str(
+            r#"
+            preset = "emoji"
+            projects = [{ path = "/work/*", icon = "W" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(right(&config), ["W", "📁"]);
+    }
+
+    #[test]
+    fn servers() {
+        let tmux = fake();
+        let work = Server::Name("work".to_string());
+        tmux.add_pane(&work, "work", "logs", "tail");
+
+        let mut config = Config::default();
+        config.servers = vec![work];
+        let feed = feed(&tmux, &config);
+
+        assert_eq!(
+            ids(&feed),
+            [
+                "[sep]",
+                "$0",
+                "@1",
+                "@4",
+                "[sep]",
+                "$6",
+                "@7",
+                "[sep]",
+                "[sep]",
+                "-Lwork:$9",
+                "-Lwork:@10",
+            ]
+        );
+        assert!(console::strip_ansi_codes(&feed[0]).ends_with("[default]"));
+        assert!(console::strip_ansi_codes(&feed[8]).ends_with("[work]"));
+    }
+
+    #[test]
+    fn icons() {
+        let mut config = Config::default();
+        config.icons = toml::from_str(
+            r#"
+            preset = "emoji"
+            commands = true
+            sessions = [{ name = "/^b/", icon = "B" }]
+            windows = [{ command = "zsh", path = "/nowhere", icon = "N" }]
+            "#,
+        )
+        .unwrap();
+        config
+            .session_icons
+            .insert("alpha".to_string(), "A".to_string());
+        let feed = feed(&fake(), &config);
+
+        let text = |i: usize| console::strip_ansi_codes(&feed[i]).into_owned();
+        assert!(text(0).contains("A   alpha"), "{:?}", text(0));
+        assert!(text(4).contains("B   beta"), "{:?}", text(4));
+        // window icons are padded to the widest one
+        assert!(text(2).contains("🐚 run"), "{:?}", text(2));
+    }
+
+    #[test]
+    fn wide_names() {
+        let width = |session: &str, window: &str, title: &str| {
+            let tmux = fake();
+            tmux.add_pane(&Server::Default, session, window, title);
+            let mut config = Config::default();
+            config.show_panes = true;
+            let snapshots = snapshot::create_all(&tmux, &[]).unwrap();
+            Formatter::new(&snapshots, &config).width
+        };
+
+        // as wide as ascii names taking the same columns
+        assert_eq!(
+            width("日本語のセッション", "編集する窓", "🦀🦀🦀"),
+            width("abcdefghijklmnopqr", "abcdefghij", "abcdef")
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let tmux = fake();
+        let name = "a-very-long-session-name";
+        tmux.add_pane(&Server::Default, name, "a-very-long-window-name", "zsh");
+        let snapshots = snapshot::create_all(&tmux, &[]).unwrap();
+        let config = Config::default();
+
+        let wide = Formatter::new(&snapshots, &config);
+        assert!(wide.width > 60);
+
+        let formatter = Formatter::with_max_width(&snapshots, &config, Some(60));
+        assert_eq!(formatter.width, 60);
+        for line in &formatter.feed {
+            let (id, text) = line.split_once('\t').unwrap();
+            if id.starts_with('@') {
+                assert_eq!(measure_text_width(text) + LEFT_MARGIN, 60, "{:?}", text);
+            }
+        }
+
+        let last = |f: &Formatter| console::strip_ansi_codes(f.feed.last().unwrap()).into_owned();
+        assert!(last(&formatter).contains("- a-very-long-window-…"));
+        assert!(last(&formatter).ends_with(" a-very-long-session-name:0"));
+
+        // then session names in part 2
+        let formatter = Formatter::with_max_width(&snapshots, &config, Some(40));
+        assert_eq!(formatter.width, 40);
+        assert!(
+            last(&formatter).ends_with(" a-very-long-ses…:0"),
+            "{:?}",
+            last(&formatter)
+        );
+
+        // not wider than needed
+        let formatter = Formatter::with_max_width(&snapshots, &config, Some(200));
+        assert_eq!(formatter.width, wide.width);
+    }
+
+    #[test]
+    fn aligned() {
+        let mut config = Config::default();
+        config.show_panes = true;
+        let snapshots = snapshot::create_all(&fake(), &[]).unwrap();
+        let formatter = Formatter::new(&snapshots, &config);
+
+        for line in &formatter.feed {
+            let (id, text) = line.split_once('\t').unwrap();
+            if id.starts_with('@') || id.starts_with('%') {
+                let width = console::measure_text_width(text);
+                assert_eq!(width + LEFT_MARGIN, formatter.width, "{:?}", text);
+            }
+        }
+    }
+}
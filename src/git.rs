@@ -0,0 +1,241 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use git2::{Branch, ErrorCode, Repository, StatusOptions};
+
+use crate::logging::*;
+
+/// State of a git working tree, read from `.git` without touching remotes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GitStatus {
+    /// Branch name, or short commit id if HEAD is detached.
+    pub branch: String,
+    /// Uncommitted changes, untracked files included.
+    pub dirty: bool,
+    /// Commits not in upstream branch.
+    pub ahead: usize,
+    /// Commits of upstream branch not in local one.
+    pub behind: usize,
+}
+
+impl GitStatus {
+    /// Read status of `repo`, which has a working tree.
+    fn read(repo: &Repository) -> Result<GitStatus, git2::Error> {
+        let mut status = GitStatus::default();
+
+        match repo.head() {
+            Ok(head) => {
+                if head.is_branch() {
+                    status.branch = head.shorthand().unwrap_or_default().to_string();
+
+                    let local = head.target();
+                    let upstream = Branch::wrap(head).upstream().ok();
+                    let upstream = upstream.and_then(|b| b.get().target());
+                    if let (Some(local), Some(upstream)) = (local, upstream) {
+                        let (ahead, behind) = repo.graph_ahead_behind(local, upstream)?;
+                        status.ahead = ahead;
+                        status.behind = behind;
+                    }
+                } else if let Some(id) = head.target() {
+                    let id = id.to_string();
+                    status.branch = id[..7.min(id.len())].to_string();
+                }
+            }
+            // no commit yet
+            Err(error) if error.code() == ErrorCode::UnbornBranch => {
+                let head = repo.find_reference("HEAD")?;
+                let target = head.symbolic_target().unwrap_or_default();
+                status.branch = target.trim_start_matches("refs/heads/").to_string();
+            }
+            Err(error) => return Err(error),
+        }
+
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).exclude_submodules(true);
+        status.dirty = !repo.statuses(Some(&mut options))?.is_empty();
+
+        Ok(status)
+    }
+
+    /// Branch, `*` if dirty, then ahead and behind counts, e.g. `main* ↑1↓2`.
+    pub fn summary(&self) -> String {
+        let mut s = self.branch.clone();
+        if self.dirty {
+            s.push('*');
+        }
+        if self.ahead > 0 || self.behind > 0 {
+            s.push(' ');
+        }
+        if self.ahead > 0 {
+            s.push_str(&format!("↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            s.push_str(&format!("↓{}", self.behind));
+        }
+        s
+    }
+}
+
+/// Git status of directories, each working tree is read once.
+#[derive(Default)]
+pub struct GitCache {
+    /// By directory, `None` if not in a working tree.
+    dirs: RefCell<HashMap<PathBuf, Option<Rc<GitStatus>>>>,
+    /// By root of working tree.
+    roots: RefCell<HashMap<PathBuf, Rc<GitStatus>>>,
+}
+
+impl GitCache {
+    pub fn new() -> GitCache {
+        GitCache::default()
+    }
+
+    /// Status of working tree containing `dir`, `None` if there is none.
+    pub fn status(&self, dir: &str) -> Option<Rc<GitStatus>> {
+        if dir.is_empty() {
+            return None;
+        }
+
+        let dir = Path::new(dir);
+        if let Some(status) = self.dirs.borrow().get(dir) {
+            return status.clone();
+        }
+
+        let status = self.read(dir);
+        self.dirs
+            .borrow_mut()
+            .insert(dir.to_path_buf(), status.clone());
+        status
+    }
+
+    fn read(&self, dir: &Path) -> Option<Rc<GitStatus>> {
+        let repo = Repository::discover(dir).ok()?;
+        let root = repo.workdir()?.to_path_buf();
+
+        if let Some(status) = self.roots.borrow().get(&root) {
+            return Some(status.clone());
+        }
+
+        let status = match GitStatus::read(&repo) {
+            Ok(status) => Rc::new(status),
+            Err(error) => {
+                warn!("failed to read git status of {:?}: {}", root, error);
+                return None;
+            }
+        };
+        debug!("git status of {:?}: {:?}", root, status);
+
+        self.roots.borrow_mut().insert(root, status.clone());
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::{BranchType, Oid, Signature};
+
+    use super::*;
+    use crate::testing::temp_dir;
+
+    fn temp_repo(name: &str) -> (PathBuf, Repository) {
+        let dir = temp_dir(name);
+        let repo = Repository::init(&dir).unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+        (dir, repo)
+    }
+
+    fn commit(repo: &Repository, file: &str) -> Oid {
+        let root = repo.workdir().unwrap();
+        fs::write(root.join(file), file).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let signature = Signature::now("tav", "tav@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, file, &tree, &parents)
+            .unwrap()
+    }
+
+    fn status(dir: &Path) -> GitStatus {
+        let cache = GitCache::new();
+        (*cache.status(dir.to_str().unwrap()).unwrap()).clone()
+    }
+
+    #[test]
+    fn branch_and_dirty() {
+        let (dir, repo) = temp_repo("git-status");
+
+        // no commit yet
+        assert_eq!(status(&dir).branch, "main");
+
+        commit(&repo, "a");
+        let clean = status(&dir);
+        assert_eq!(clean.branch, "main");
+        assert!(!clean.dirty);
+        assert_eq!(clean.summary(), "main");
+
+        fs::write(dir.join("untracked"), "").unwrap();
+        let dirty = status(&dir);
+        assert!(dirty.dirty);
+        assert_eq!(dirty.summary(), "main*");
+
+        // subdirectory of working tree
+        fs::create_dir_all(dir.join("src")).unwrap();
+        assert_eq!(status(&dir.join("src")).branch, "main");
+
+        // detached
+        let id = repo.head().unwrap().target().unwrap();
+        repo.set_head_detached(id).unwrap();
+        assert_eq!(status(&dir).branch, id.to_string()[..7]);
+    }
+
+    #[test]
+    fn ahead_behind() {
+        let (dir, repo) = temp_repo("git-ahead-behind");
+        let first = commit(&repo, "a");
+        repo.branch("base", &repo.find_commit(first).unwrap(), false)
+            .unwrap();
+        commit(&repo, "b");
+        commit(&repo, "c");
+
+        let mut main = repo.find_branch("main", BranchType::Local).unwrap();
+        main.set_upstream(Some("base")).unwrap();
+
+        let ahead = status(&dir);
+        assert_eq!((ahead.ahead, ahead.behind), (2, 0));
+        assert_eq!(ahead.summary(), "main ↑2");
+
+        repo.set_head("refs/heads/base").unwrap();
+        repo.find_branch("base", BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("main"))
+            .unwrap();
+        assert_eq!(status(&dir).summary(), "base* ↓2");
+    }
+
+    #[test]
+    fn cache() {
+        let (dir, repo) = temp_repo("git-cache");
+        commit(&repo, "a");
+
+        let cache = GitCache::new();
+        let status = cache.status(dir.to_str().unwrap()).unwrap();
+        assert!(!status.dirty);
+
+        // read once, later changes are not seen
+        fs::write(dir.join("b"), "").unwrap();
+        let again = cache.status(dir.to_str().unwrap()).unwrap();
+        assert!(Rc::ptr_eq(&status, &again));
+
+        assert_eq!(cache.status(""), None);
+        assert_eq!(cache.status("/"), None);
+    }
+}
@@ -0,0 +1,396 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Popup border drawn by `fzf-tmux`, on each side.
+const BORDER: usize = 1;
+/// Lines used by fzf besides the list: prompt line and the (empty) header.
+const CHROME_LINES: usize = 2;
+/// Column reserved for fzf scroll bar.
+const SCROLLBAR: usize = 1;
+
+/// A length in terminal cells, or a percentage of the containing length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Cells(usize),
+    Percent(usize),
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("invalid length: {:?}", s);
+
+        match s.strip_suffix('%') {
+            Some(n) => {
+                let n: usize = n.parse().map_err(|_| invalid())?;
+                if n >= 100 {
+                    return Err(invalid());
+                }
+                Ok(Length::Percent(n))
+            }
+            None => s.parse().map(Length::Cells).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Value of fzf `--margin`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Margin {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl FromStr for Margin {
+    type Err = String;
+
+    /// Parse `TRBL`, `TB,RL`, `T,RL,B` or `T,R,B,L` as fzf does.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let v = s
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<Length>, String>>()?;
+
+        let (top, right, bottom, left) = match v.as_slice() {
+            [a] => (*a, *a, *a, *a),
+            [tb, rl] => (*tb, *rl, *tb, *rl),
+            [t, rl, b] => (*t, *rl, *b, *rl),
+            [t, r, b, l] => (*t, *r, *b, *l),
+            _ => return Err(format!("invalid margin: {:?}", s)),
+        };
+
+        Ok(Margin {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+}
+
+/// Popup width or height in config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(try_from = "RawSize")]
+pub enum Size {
+    /// Fit content.
+    #[default]
+    Auto,
+    /// Fixed size in cells.
+    Cells(usize),
+    /// Percentage of tmux client size.
+    Percent(usize),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSize {
+    Number(usize),
+    Text(String),
+}
+
+impl TryFrom<RawSize> for Size {
+    type Error = String;
+
+    fn try_from(raw: RawSize) -> Result<Self, Self::Error> {
+        match raw {
+            RawSize::Number(n) => Ok(Size::Cells(n)),
+            RawSize::Text(s) if s == "auto" => Ok(Size::Auto),
+            RawSize::Text(s) => match s.parse()? {
+                Length::Cells(n) => Ok(Size::Cells(n)),
+                Length::Percent(n) => Ok(Size::Percent(n)),
+            },
+        }
+    }
+}
+
+/// The `[popup]` section.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PopupOptions {
+    pub width: Size,
+    pub height: Size,
+}
+
+/// Return outer size of a popup which shows `content` (columns, lines of the
+/// list), surrounded by fzf `margin`, clamped to `client` size.
+pub fn popup_size(
+    content: (usize, usize),
+    margin: &Margin,
+    client: (usize, usize),
+    options: &PopupOptions,
+) -> (usize, usize) {
+    let (content_width, content_height) = content;
+    let (client_width, client_height) = client;
+
+    let width = match options.width {
+        Size::Auto => fit(
+            content_width + SCROLLBAR,
+            margin.left,
+            margin.right,
+            2 * BORDER,
+        ),
+        Size::Cells(n) => n,
+        Size::Percent(n) => client_width * n / 100,
+    };
+
+    let height = match options.height {
+        Size::Auto => fit(
+            content_height + CHROME_LINES,
+            margin.top,
+            margin.bottom,
+            2 * BORDER,
+        ),
+        Size::Cells(n) => n,
+        Size::Percent(n) => client_height * n / 100,
+    };
+
+    (width.min(client_width), height.min(client_height))
+}
+
+/// Return size of `content` (columns, lines of the list) enlarged to make room
+/// for fzf preview window given by `spec` (value of `--preview-window`), so
+/// that the list keeps its size.
+pub fn with_preview(content: (usize, usize), spec: &str) -> (usize, usize) {
+    let (width, height) = content;
+
+    let (beside, size) = match preview_window(spec) {
+        Some(preview) => preview,
+        None => return content,
+    };
+
+    // preview window has its own border, 2 cells on the side next to list
+    let grow = |n: usize| match size {
+        Length::Cells(cells) => n + cells + 2,
+        Length::Percent(percent) => fit(n, Length::Percent(percent), Length::Cells(0), 0),
+    };
+
+    if beside {
+        (grow(width), height)
+    } else {
+        (width, grow(height))
+    }
+}
+
+/// Return columns left for the list in a popup on a client `client_width`
+/// columns wide, the inverse of `popup_size` and `with_preview`.
+pub fn popup_list_width(
+    client_width: usize,
+    margin: &Margin,
+    options: &PopupOptions,
+    preview: Option<&str>,
+) -> usize {
+    let width = match options.width {
+        Size::Auto => client_width,
+        Size::Cells(n) => n.min(client_width),
+        Size::Percent(n) => client_width * n / 100,
+    };
+    list_width(width.saturating_sub(2 * BORDER), margin, preview)
+}
+
+/// Return columns left for the list in fzf `width` columns wide, after
+/// `margin`, scroll bar and preview window given by `preview` (value of
+/// `--preview-window`) are taken.
+pub fn list_width(width: usize, margin: &Margin, preview: Option<&str>) -> usize {
+    let mut inner = width;
+    for length in &[margin.left, margin.right] {
+        inner = inner.saturating_sub(match length {
+            Length::Cells(n) => *n,
+            Length::Percent(n) => width * n / 100,
+        });
+    }
+    inner = inner.saturating_sub(SCROLLBAR);
+
+    match preview.and_then(preview_window) {
+        Some((true, Length::Cells(cells))) => inner.saturating_sub(cells + 2),
+        Some((true, Length::Percent(percent))) => inner * (100 - percent) / 100,
+        _ => inner,
+    }
+}
+
+/// Parse `--preview-window` spec, return whether the preview is beside the
+/// list (left or right), and its size. `None` if hidden.
+fn preview_window(spec: &str) -> Option<(bool, Length)> {
+    let mut position = "right";
+    let mut size = Length::Percent(50);
+    let mut hidden = false;
+
+    for token in spec.split([':', ',']) {
+        match token {
+            "up" | "down" | "left" | "right" | "top" | "bottom" => position = token,
+            "hidden" => hidden = true,
+            _ => {
+                if let Ok(length) = token.parse() {
+                    size = length;
+                }
+            }
+        }
+    }
+
+    if hidden {
+        return None;
+    }
+
+    let beside = !matches!(position, "up" | "down" | "top" | "bottom");
+    Some((beside, size))
+}
+
+/// Size of box which leaves `inner` cells after both margins and `extra`
+/// fixed cells are taken.
+///
+/// Percentage margins are relative to the popup size (inside border), as fzf
+/// computes them.
+fn fit(inner: usize, a: Length, b: Length, extra: usize) -> usize {
+    let mut cells = inner;
+    let mut percent = 0;
+
+    for margin in &[a, b] {
+        match margin {
+            Length::Cells(n) => cells += n,
+            Length::Percent(n) => percent += n,
+        }
+    }
+
+    if percent >= 100 {
+        return usize::MAX;
+    }
+
+    // size * (100 - percent) / 100 >= cells, round up
+    let size = (cells * 100).div_ceil(100 - percent);
+    size + extra
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn margin(s: &str) -> Margin {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_margin() {
+        let m = margin("3,5,3,3");
+        assert_eq!(m.top, Length::Cells(3));
+        assert_eq!(m.right, Length::Cells(5));
+        assert_eq!(m.bottom, Length::Cells(3));
+        assert_eq!(m.left, Length::Cells(3));
+
+        assert_eq!(margin("2"), margin("2,2,2,2"));
+        assert_eq!(margin("1,2"), margin("1,2,1,2"));
+        assert_eq!(margin("1,2,3"), margin("1,2,3,2"));
+        assert_eq!(margin("10%,2").top, Length::Percent(10));
+
+        assert!("1,2,3,4,5".parse::<Margin>().is_err());
+        assert!("x".parse::<Margin>().is_err());
+        assert!("100%".parse::<Margin>().is_err());
+    }
+
+    #[test]
+    fn auto_size_fits_content() {
+        let size = popup_size(
+            (50, 10),
+            &margin("3,5,3,3"),
+            (200, 60),
+            &PopupOptions::default(),
+        );
+        // 50 + scrollbar + margins + border
+        assert_eq!(size.0, 50 + 1 + 5 + 3 + 2);
+        // 10 + prompt, header + margins + border
+        assert_eq!(size.1, 10 + 2 + 3 + 3 + 2);
+    }
+
+    #[test]
+    fn percentage_margin() {
+        let size = popup_size(
+            (90, 10),
+            &margin("0,10%"),
+            (200, 60),
+            &PopupOptions::default(),
+        );
+        // 91 cells left after 20% margin in total
+        assert_eq!(size.0, 114 + 2);
+        assert!((size.0 - 2) * 80 / 100 >= 91);
+    }
+
+    #[test]
+    fn clamp_to_small_client() {
+        let size = popup_size(
+            (50, 100),
+            &margin("3,5,3,3"),
+            (30, 8),
+            &PopupOptions::default(),
+        );
+        assert_eq!(size, (30, 8));
+
+        let size = popup_size((50, 10), &margin("3"), (0, 0), &PopupOptions::default());
+        assert_eq!(size, (0, 0));
+    }
+
+    #[test]
+    fn configured_size() {
+        let options = PopupOptions {
+            width: Size::Percent(80),
+            height: Size::Cells(20),
+        };
+        let size = popup_size((50, 10), &margin("3"), (200, 60), &options);
+        assert_eq!(size, (160, 20));
+
+        let size = popup_size((50, 10), &margin("3"), (100, 15), &options);
+        assert_eq!(size, (80, 15));
+    }
+
+    #[test]
+    fn parse_size() {
+        let options: PopupOptions = toml::from_str("width = \"80%\"\nheight = 30").unwrap();
+        assert_eq!(options.width, Size::Percent(80));
+        assert_eq!(options.height, Size::Cells(30));
+
+        let options: PopupOptions = toml::from_str("width = \"auto\"").unwrap();
+        assert_eq!(options.width, Size::Auto);
+        assert_eq!(options.height, Size::Auto);
+
+        assert!(toml::from_str::<PopupOptions>("width = \"x\"").is_err());
+    }
+
+    #[test]
+    fn preview_window() {
+        assert_eq!(with_preview((50, 10), "right:50%"), (100, 10));
+        assert_eq!(with_preview((50, 10), "down,40%"), (50, 17));
+        assert_eq!(with_preview((50, 10), "left:30"), (82, 10));
+        assert_eq!(with_preview((50, 10), "right:50%:hidden"), (50, 10));
+    }
+
+    #[test]
+    fn available_list_width() {
+        // the inverse of `popup_size`
+        let size = popup_size(
+            (50, 10),
+            &margin("3,5,3,3"),
+            (200, 60),
+            &PopupOptions::default(),
+        );
+        let options = PopupOptions {
+            width: Size::Cells(size.0),
+            height: Size::Auto,
+        };
+        assert_eq!(
+            popup_list_width(200, &margin("3,5,3,3"), &options, None),
+            50
+        );
+
+        let options = PopupOptions::default();
+        assert_eq!(popup_list_width(100, &margin("0"), &options, None), 97);
+        assert_eq!(popup_list_width(30, &margin("20"), &options, None), 0);
+
+        assert_eq!(list_width(100, &margin("0,10%"), None), 79);
+        assert_eq!(list_width(100, &margin("0"), Some("right:50%")), 49);
+        assert_eq!(list_width(100, &margin("0"), Some("left:30")), 67);
+        assert_eq!(list_width(100, &margin("0"), Some("down:50%")), 99);
+        assert_eq!(list_width(100, &margin("0"), Some("right:hidden")), 99);
+    }
+}
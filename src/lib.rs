@@ -1,6 +1,18 @@
 pub mod config;
+pub mod error;
 pub mod logging;
 pub mod run;
+pub mod tmux;
 
+mod action;
 mod fzf;
-mod tmux;
+mod git;
+mod layout;
+mod list;
+mod save;
+mod session;
+mod shell;
+mod ui;
+
+#[cfg(test)]
+mod testing;
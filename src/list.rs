@@ -0,0 +1,313 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::Serialize;
+
+use crate::config::{Config, ListFormat};
+use crate::fzf::Formatter;
+use crate::tmux::snapshot::{id_number, Snapshot};
+
+const TSV_HEADER: [&str; 11] = [
+    "server",
+    "session_id",
+    "session_name",
+    "window_id",
+    "window_index",
+    "window_name",
+    "pane_id",
+    "pane_index",
+    "pane_title",
+    "pane_current_path",
+    "pane_current_command",
+];
+
+/// Everything tav knows about, as serialized by `tav list --format json`.
+#[derive(Serialize)]
+struct Listing<'a> {
+    servers: &'a [Snapshot],
+    dead_sessions: Vec<Named<'a>>,
+    projects: Vec<Named<'a>>,
+}
+
+/// A dead session with its script or definition, or a project with its
+/// directory.
+#[derive(Serialize)]
+struct Named<'a> {
+    name: &'a str,
+    path: &'a Path,
+}
+
+/// Render `snapshots` and dead sessions in `format`.
+pub fn render(snapshots: &[Snapshot], config: &Config, format: ListFormat) -> String {
+    match format {
+        ListFormat::Feed => {
+            let formatter = Formatter::new(snapshots, config);
+            format!("{}\n", formatter.feed.join("\n"))
+        }
+        ListFormat::Json => {
+            let listing = Listing {
+                servers: snapshots,
+                dead_sessions: dead_sessions(snapshots, config),
+                projects: projects(snapshots, config),
+            };
+            format!("{}\n", serde_json::to_string_pretty(&listing).unwrap())
+        }
+        ListFormat::Tsv => tsv(snapshots, config),
+        ListFormat::Plain => plain(snapshots, config),
+    }
+}
+
+/// Whether session `name` is alive on the default server.
+fn is_alive(snapshots: &[Snapshot], name: &str) -> bool {
+    snapshots
+        .first()
+        .is_some_and(|snapshot| snapshot.sessions.values().any(|s| s.borrow().name == name))
+}
+
+/// Dead sessions which are not alive on the default server.
+fn dead_sessions<'a>(snapshots: &[Snapshot], config: &'a Config) -> Vec<Named<'a>> {
+    config
+        .dead_session
+        .names
+        .iter()
+        .filter(|name| !is_alive(snapshots, name))
+        .filter_map(|name| {
+            let path = config.dead_session.paths.get(name)?;
+            Some(Named { name, path })
+        })
+        .collect()
+}
+
+/// Projects with neither live nor dead session of the same name.
+fn projects<'a>(snapshots: &[Snapshot], config: &'a Config) -> Vec<Named<'a>> {
+    config
+        .project
+        .names
+        .iter()
+        .filter(|name| !is_alive(snapshots, name))
+        .filter(|name| !config.dead_session.names.contains(name))
+        .filter_map(|name| {
+            let path = config.project.paths.get(name)?;
+            Some(Named { name, path })
+        })
+        .collect()
+}
+
+fn tsv(snapshots: &[Snapshot], config: &Config) -> String {
+    let mut s = TSV_HEADER.join("\t");
+    s.push('\n');
+
+    for snapshot in snapshots {
+        for session in sorted(&snapshot.sessions, |s| id_number(&s.id)) {
+            let session = session.borrow();
+            for window in sorted(&session.windows, |w| w.index) {
+                let window = window.borrow();
+                for pane in sorted(&window.panes, |p| p.index) {
+                    let pane = pane.borrow();
+                    let fields = [
+                        snapshot.server.to_string(),
+                        session.id.clone(),
+                        session.name.clone(),
+                        window.id.clone(),
+                        window.index.to_string(),
+                        window.name.clone(),
+                        pane.id.clone(),
+                        pane.index.to_string(),
+                        pane.title.clone(),
+                        pane.current_path.clone(),
+                        pane.current_command.clone(),
+                    ];
+                    push_row(&mut s, &fields);
+                }
+            }
+        }
+    }
+
+    let dead = dead_sessions(snapshots, config)
+        .into_iter()
+        .map(|d| ("[dead]", d));
+    let projects = projects(snapshots, config)
+        .into_iter()
+        .map(|p| ("[project]", p));
+    for (kind, named) in dead.chain(projects) {
+        let mut fields = vec![String::new(); TSV_HEADER.len()];
+        fields[1] = kind.to_string();
+        fields[2] = named.name.to_string();
+        fields[9] = named.path.display().to_string();
+        push_row(&mut s, &fields);
+    }
+
+    s
+}
+
+/// Append a line of tab separated `fields` to `s`.
+fn push_row(s: &mut String, fields: &[String]) {
+    let fields: Vec<String> = fields.iter().map(|f| escape(f)).collect();
+    s.push_str(&fields.join("\t"));
+    s.push('\n');
+}
+
+/// Escape backslash, tab and line breaks in a TSV field as `\\`, `\t`, `\n`
+/// and `\r`, which would otherwise split fields or rows.
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn plain(snapshots: &[Snapshot], config: &Config) -> String {
+    let mut s = String::new();
+
+    for snapshot in snapshots {
+        if snapshots.len() > 1 {
+            let _ = writeln!(s, "[{}]", snapshot.server);
+        }
+
+        for session in sorted(&snapshot.sessions, |s| id_number(&s.id)) {
+            let session = session.borrow();
+            let _ = writeln!(s, "{} {}", session.id, session.name);
+
+            for window in sorted(&session.windows, |w| w.index) {
+                let window = window.borrow();
+                let _ = writeln!(s, "  {} {}:{}", window.id, window.index, window.name);
+
+                for pane in sorted(&window.panes, |p| p.index) {
+                    let pane = pane.borrow();
+                    let _ = writeln!(
+                        s,
+                        "    {} {}.{} {} {}",
+                        pane.id, window.index, pane.index, pane.current_command, pane.current_path
+                    );
+                }
+            }
+        }
+    }
+
+    for dead in dead_sessions(snapshots, config) {
+        let _ = writeln!(s, "[dead] {} {}", dead.name, dead.path.display());
+    }
+    for project in projects(snapshots, config) {
+        let _ = writeln!(s, "[project] {} {}", project.name, project.path.display());
+    }
+
+    s
+}
+
+/// Values of `map` sorted by `key`.
+fn sorted<T, K: Ord>(
+    map: &HashMap<String, Rc<RefCell<T>>>,
+    key: impl Fn(&T) -> K,
+) -> Vec<Rc<RefCell<T>>> {
+    let mut items: Vec<Rc<RefCell<T>>> = map.values().cloned().collect();
+    items.sort_by_key(|item| key(&item.borrow()));
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testing::fake;
+    use crate::tmux::snapshot;
+
+    fn snapshots() -> Vec<Snapshot> {
+        snapshot::create_all(&fake(), &[]).unwrap()
+    }
+
+    /// Config with dead sessions `alpha` and `gone`, projects `beta` and
+    /// `new`.
+    fn config() -> Config {
+        let mut config = Config::default();
+        for name in ["alpha", "gone"] {
+            let path = PathBuf::from(format!("/sessions/{}.toml", name));
+            config.dead_session.names.push(name.to_string());
+            config.dead_session.paths.insert(name.to_string(), path);
+        }
+        for name in ["beta", "gone", "new"] {
+            let path = PathBuf::from(format!("/code/{}", name));
+            config.project.add(path);
+        }
+        config
+    }
+
+    #[test]
+    fn tsv_rows() {
+        let output = tsv(&snapshots(), &config());
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines[0], TSV_HEADER.join("\t"));
+        assert_eq!(
+            lines[1],
+            "default\t$0\talpha\t@1\t0\tedit\t%2\t0\tnvim\t/home/alpha\tzsh"
+        );
+        assert_eq!(
+            lines[4],
+            "default\t$6\tbeta\t@7\t0\tlogs\t%8\t0\ttail\t/home/beta\tzsh"
+        );
+        // live, dead or listed sessions are not offered again
+        assert_eq!(
+            lines[5..],
+            [
+                "\t[dead]\tgone\t\t\t\t\t\t\t/sessions/gone.toml\t",
+                "\t[project]\tnew\t\t\t\t\t\t\t/code/new\t",
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv_escapes_fields() {
+        assert_eq!(escape("a\tb\nc\rd\\e"), "a\\tb\\nc\\rd\\\\e");
+
+        let mut config = Config::default();
+        config.project.add(PathBuf::from("/code/tab\there"));
+        let output = tsv(&[], &config);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].split('\t').count(), TSV_HEADER.len());
+        assert!(lines[1].contains("/code/tab\\there"));
+    }
+
+    #[test]
+    fn plain_tree() {
+        let output = plain(&snapshots(), &config());
+        assert_eq!(
+            output,
+            "\
+$0 alpha
+  @1 0:edit
+    %2 0.0 zsh /home/alpha
+    %3 0.1 zsh /home/alpha
+  @4 1:run
+    %5 1.0 zsh /home/alpha
+$6 beta
+  @7 0:logs
+    %8 0.0 zsh /home/beta
+[dead] gone /sessions/gone.toml
+[project] new /code/new
+"
+        );
+    }
+
+    #[test]
+    fn json_listing() {
+        let output = render(&snapshots(), &config(), ListFormat::Json);
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(value["servers"].as_array().unwrap().len(), 1);
+        assert_eq!(value["dead_sessions"][0]["name"], "gone");
+        assert_eq!(value["projects"][0]["name"], "new");
+        assert_eq!(value["projects"][0]["path"], "/code/new");
+    }
+}
@@ -1,60 +1,181 @@
-use std::io::Write;
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, Write};
 use std::process::{Command, Stdio};
-use std::str;
 
-use crate::config::{Config, Task};
+use console::style;
+
+use crate::action::{Action, Choice};
+use crate::config::{Backend, Config, Task};
+use crate::error::{Result, TavError};
 use crate::fzf::Formatter;
+use crate::layout::{self, Margin};
+use crate::list;
 use crate::logging::*;
-use crate::tmux::{cmd as tmux, snapshot};
+use crate::save;
+use crate::session::SessionDef;
+use crate::shell::quote;
+use crate::tmux::{cmd::Process, control::Control, server::Server, snapshot, TmuxBackend};
+use crate::ui;
+
+// content width of the rename prompt popup
+const PROMPT_WIDTH: usize = 50;
 
 pub fn run(config: Config) {
     console::set_colors_enabled(true);
 
-    if !tmux::is_in_tmux() {
-        std::eprintln!("Not run in tmux environment");
-        return;
+    // no popup without tmux client, show picker in terminal and attach to
+    // the chosen target
+    let mut config = config;
+    if !Process.is_in_tmux() {
+        if let Task::Popup = config.task {
+            config.task = Task::Fzf;
+        }
     }
 
+    // a single capture for preview is not worth a connection
+    let result = match (config.backend, &config.task) {
+        (Backend::Control, Task::Preview { .. }) | (Backend::Process, _) => {
+            execute(&Process, config)
+        }
+        (Backend::Control, _) => execute(&Control::default(), config),
+    };
+
+    if let Err(error) = result {
+        error!("{:?}", error);
+        std::eprintln!("tav: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn execute(tmux: &dyn TmuxBackend, config: Config) -> Result<()> {
     match config.task {
-        Task::Popup => match choose_window(config.clone()) {
-            Some(id) => {
-                if id.starts_with('$') || id.starts_with('@') {
-                    tmux::switch_to(&id);
-                } else if let Some(name) = id.strip_prefix("[dead]") {
-                    create_session(name, config);
+        // show picker again after killing or renaming
+        Task::Popup | Task::Fzf | Task::Ui => loop {
+            let choice = match choose_window(tmux, config.clone())? {
+                Some(choice) => choice,
+                None => {
+                    debug!("quit with noop");
+                    break;
                 }
+            };
+
+            if act(tmux, &choice, &config)? {
+                break;
             }
-            None => debug!("quit with noop"),
         },
-        Task::Fzf => {}
-        Task::List => {
-            let snapshot = snapshot::create();
-            let formatter = Formatter::new(&snapshot, &config);
-            println!("{}", &formatter.feed.join("\n"));
+        Task::List { format } => {
+            let snapshots = snapshot::create_all(tmux, &config.servers)?;
+            print!("{}", list::render(&snapshots, &config, format));
         }
+        Task::Save {
+            ref session,
+            toml,
+            force,
+        } => {
+            let snapshot = snapshot::create(tmux)?;
+            let session = save::find_session(&snapshot, session.as_deref())?;
+            let path = save::save(&session.borrow(), &config, toml, force)?;
+            println!("saved to {}", path.display());
+        }
+        Task::Preview { ref id } => print!("{}", preview(tmux, id, &config)?),
     }
+
+    Ok(())
 }
 
-/// Run `fzf-cmd` to let user choose a window.
-fn choose_window(config: Config) -> Option<String> {
+/// Carry out the choice, return `true` if the picker is done.
+fn act(tmux: &dyn TmuxBackend, choice: &Choice, config: &Config) -> Result<bool> {
+    match choice.action {
+        Action::Switch => {
+            switch(tmux, &choice.id, config)?;
+            return Ok(true);
+        }
+        Action::Kill => {
+            if !choice.id.starts_with('[') {
+                tmux.kill(&choice.id)?;
+            }
+        }
+        Action::Rename => {
+            if let Some('$' | '@') = sigil(&choice.id) {
+                let name = tmux.name_of(&choice.id)?;
+                if let Some(name) = prompt_name(tmux, config, &name)? {
+                    tmux.rename(&choice.id, &name)?;
+                }
+            }
+        }
+        Action::New => {
+            // nothing to name it after, show picker again
+            let name = choice.query.trim();
+            if name.is_empty() {
+                return Ok(false);
+            }
+            let id = tmux.new_session(name, None)?;
+            tmux.switch_to(&id)?;
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Switch to chosen line, resurrect it if it is a dead session, create a
+/// session rooted in it if it is a project.
+fn switch(tmux: &dyn TmuxBackend, id: &str, config: &Config) -> Result<()> {
+    match sigil(id) {
+        Some('$' | '@') => tmux.switch_to(id)?,
+        Some('%') => tmux.switch_to_pane(id)?,
+        _ => {
+            if let Some(name) = id.strip_prefix("[dead]") {
+                create_session(tmux, name, config.clone())?;
+            } else if let Some(name) = id.strip_prefix("[project]") {
+                open_project(tmux, name, config)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Content of preview window for line `id`.
+fn preview(tmux: &dyn TmuxBackend, id: &str, config: &Config) -> Result<String> {
+    // separator lines have nothing to show
+    if let Some('$' | '@' | '%') = sigil(id) {
+        tmux.capture(id)
+    } else if let Some(name) = id.strip_prefix("[dead]") {
+        preview_dead_session(name, config)
+    } else if let Some(name) = id.strip_prefix("[project]") {
+        preview_project(name, config)
+    } else {
+        Ok(String::new())
+    }
+}
+
+/// Leading `$`, `@` or `%` of tmux id, which may be qualified with server.
+fn sigil(id: &str) -> Option<char> {
+    Server::split(id).1.chars().next()
+}
+
+/// Run `fzf-tmux` (for `Task::Popup`), plain `fzf` (for `Task::Fzf`) or the
+/// builtin picker (for `Task::Ui`) to let user choose a window.
+///
+/// Fallback to the builtin picker if fzf is not installed.
+fn choose_window(tmux: &dyn TmuxBackend, config: Config) -> Result<Option<Choice>> {
     //
     // take snapshot
     //
 
-    let snapshot = snapshot::create();
-    let (_, client_height) = tmux::client_size();
+    let snapshots = snapshot::create_all(tmux, &config.servers)?;
 
     //
     // generate fzf feed
     //
 
-    let formatter = Formatter::new(&snapshot, &config);
-    let width = formatter.width + 4 * 2 + 5 - 2; // 💀 magic number
+    let formatter = Formatter::with_max_width(&snapshots, &config, list_width(tmux, &config));
     debug!("feed height: {}", formatter.height);
 
-    let mut height = formatter.height + 2 * 2 + 5 + 1;
-    height = height.min(client_height - 16);
+    if let Task::Ui = config.task {
+        return Ok(choose_in_ui(&formatter, &config));
+    }
 
     let feed = formatter.feed.join("\n");
 
@@ -62,14 +183,11 @@ fn choose_window(config: Config) -> Option<String> {
     // choose window id
     //
 
-    let mut cmd = Command::new("fzf-tmux");
-    cmd.env("FZF_DEFAULT_OPTS", ""); // reset env effect
-
-    // popup
-    cmd.arg("-w")
-        .arg(width.to_string())
-        .arg("-h")
-        .arg(height.to_string());
+    let mut content = (formatter.width, formatter.height);
+    if config.fzf.preview {
+        content = layout::with_preview(content, &config.fzf.preview_window);
+    }
+    let mut cmd = fzf_command(tmux, &config, content)?;
 
     // search
     cmd
@@ -81,71 +199,555 @@ fn choose_window(config: Config) -> Option<String> {
         .arg("--tiebreak=end");
 
     // appearance
-    cmd.arg("--color=bg:-1,bg+:-1") // transparent background
-        .arg("--layout=reverse")
-        .arg("--ansi")
-        .arg("--margin=3,5,3,3") // WARN: magic number
+    cmd.arg("--ansi")
         .arg("--inline-info")
         .arg("--header")
         .arg("") // sepratate line
-        .arg("--prompt=▶ ")
-        .arg("--pointer=▶");
+        .arg(format!("--prompt={}", config.theme.prompt))
+        .arg(format!("--pointer={}", config.theme.pointer));
+
+    // start on current window
+    if let Some(line) = formatter.current_line {
+        cmd.arg("--bind").arg(format!("load:pos({})", line + 1));
+    }
+
+    // preview
+    if config.fzf.preview {
+        let command: Vec<String> = tav_command(&config)?.iter().map(|a| quote(a)).collect();
+        cmd.arg(format!("--preview={} preview -- {{1}}", command.join(" ")))
+            .arg(format!("--preview-window={}", config.fzf.preview_window));
+    }
+
+    // actions
+    cmd.arg("--print-query")
+        .arg(format!("--expect={}", config.keys.expect()));
 
     // key bindings
-    cmd.arg("--bind")
-        .arg("ctrl-j:page-down")
-        .arg("--bind")
-        .arg("ctrl-k:page-up")
-        .arg("--bind")
-        .arg("ctrl-f:page-down")
-        .arg("--bind")
-        .arg("ctrl-b:page-up");
+    for bind in &config.fzf.bind {
+        cmd.arg("--bind").arg(bind);
+    }
+
+    cmd.args(&config.fzf.args);
+
+    // `fzf-tmux` opens its popup on the client tmux takes for calling one
+    tmux.disconnect();
+    match run_fzf(cmd, feed) {
+        Ok(output) => {
+            let choice = output.and_then(|o| Choice::from_fzf_output(&o, &config.keys));
+            debug!("choice: {:?}", choice);
+            Ok(choice)
+        }
+        Err(error) if is_not_found(&error) => {
+            warn!("`fzf` not found, fallback to builtin picker");
+            match config.task {
+                Task::Popup => {
+                    let mut command = tav_command(&config)?;
+                    command.push("ui".to_string());
+                    let command: Vec<&str> = command.iter().map(String::as_str).collect();
+
+                    let (width, height) = popup_size(tmux, &config, content)?;
+                    tmux.popup(width, height, &command)?;
+                    Ok(None)
+                }
+                _ => Ok(choose_in_ui(&formatter, &config)),
+            }
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Command line running tav with the same config as this process, the
+/// executable followed by global options, to append a subcommand to.
+fn tav_command(config: &Config) -> Result<Vec<String>> {
+    let exe = std::env::current_exe().map_err(|e| TavError::io("tav", e))?;
+    let exe = exe.to_str().ok_or_else(|| TavError::NotUtf8(exe.clone()))?;
+
+    let mut command = vec![exe.to_string()];
+    command.extend(config.args()?);
+    Ok(command)
+}
+
+/// Ask for a new name, `initial` is the pre-filled input.
+fn prompt_name(tmux: &dyn TmuxBackend, config: &Config, initial: &str) -> Result<Option<String>> {
+    let label = "rename ▶ ";
+
+    let name = if let Task::Ui = config.task {
+        prompt_in_ui(label, initial)
+    } else {
+        let mut cmd = fzf_command(tmux, config, (PROMPT_WIDTH, 0))?;
+        cmd.arg("--print-query")
+            .arg("--info=hidden")
+            .arg(format!("--prompt={}", label))
+            .arg(format!("--query={}", initial));
+
+        tmux.disconnect();
+        match run_fzf(cmd, String::new()) {
+            Ok(output) => output.and_then(|o| o.lines().next().map(str::to_string)),
+            Err(error) if is_not_found(&error) => prompt_in_ui(label, initial),
+            Err(error) => return Err(error),
+        }
+    };
 
+    Ok(name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()))
+}
+
+/// Return size of popup window showing `content` (columns, lines).
+fn popup_size(
+    tmux: &dyn TmuxBackend,
+    config: &Config,
+    content: (usize, usize),
+) -> Result<(usize, usize)> {
+    let margin: Margin = config.fzf.margin.parse().map_err(TavError::Config)?;
+    let client = tmux.client_size()?;
+
+    let size = layout::popup_size(content, &margin, client, &config.popup);
+    debug!("popup size: {:?} for content {:?}", size, content);
+    Ok(size)
+}
+
+/// Columns available for the list, in popup on tmux client for
+/// `Task::Popup`, in terminal otherwise. `None` if unknown.
+fn list_width(tmux: &dyn TmuxBackend, config: &Config) -> Option<usize> {
+    let margin: Margin = config.fzf.margin.parse().ok()?;
+    let preview = Some(config.fzf.preview_window.as_str()).filter(|_| config.fzf.preview);
+
+    let width = match config.task {
+        Task::Popup => {
+            let (client_width, _) = tmux.client_size().ok()?;
+            layout::popup_list_width(client_width, &margin, &config.popup, preview)
+        }
+        _ => {
+            let (_, columns) = console::Term::stderr().size_checked()?;
+            layout::list_width(columns as usize, &margin, preview)
+        }
+    };
+    debug!("list width: {}", width);
+    Some(width)
+}
+
+/// Create `fzf-tmux` command for `Task::Popup`, plain `fzf` command otherwise,
+/// with common options set, `content` is the size of list.
+fn fzf_command(
+    tmux: &dyn TmuxBackend,
+    config: &Config,
+    content: (usize, usize),
+) -> Result<Command> {
+    let mut cmd = match config.task {
+        Task::Popup => {
+            let (width, height) = popup_size(tmux, config, content)?;
+            let mut cmd = Command::new("fzf-tmux");
+
+            // popup
+            cmd.arg("-w")
+                .arg(width.to_string())
+                .arg("-h")
+                .arg(height.to_string());
+
+            cmd
+        }
+        _ => Command::new("fzf"),
+    };
+    cmd.env("FZF_DEFAULT_OPTS", ""); // reset env effect
+
+    // appearance
+    cmd.arg(format!("--color={}", config.fzf.color))
+        .arg(format!("--layout={}", config.fzf.layout))
+        .arg(format!("--margin={}", config.fzf.margin));
+
+    Ok(cmd)
+}
+
+/// Feed `input` to fzf, return its output, `None` if aborted.
+fn run_fzf(mut cmd: Command, input: String) -> Result<Option<String>> {
     let mut child = cmd
         // pipe
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         // run
         .spawn()
-        .expect("failed to spawn `fzf` command");
+        .map_err(|e| TavError::io("fzf", e))?;
 
     child
         .stdin
         .take()
         .unwrap()
-        .write_all(&feed.into_bytes())
-        .expect("failed to write to stdin of `fzf` command");
+        .write_all(&input.into_bytes())
+        .map_err(|e| TavError::io("fzf", e))?;
 
     let output = child
         .wait_with_output()
-        .expect("failed to wait `fzf` to exit");
-    let output = str::from_utf8(output.stdout.as_slice()).unwrap();
+        .map_err(|e| TavError::io("fzf", e))?;
+
+    if output.status.code() == Some(130) {
+        return Ok(None); // aborted
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
 
-    let id = output.split('\t').take(1).collect::<String>();
-    debug!("chosen id: {:?}", id);
+fn is_not_found(error: &TavError) -> bool {
+    matches!(error, TavError::Io { source, .. } if source.kind() == io::ErrorKind::NotFound)
+}
 
-    if id.is_empty() {
+/// Run the builtin picker in current terminal.
+fn choose_in_ui(formatter: &Formatter, config: &Config) -> Option<Choice> {
+    ui::choose(&formatter.feed, formatter.current_line, config).unwrap_or_else(|error| {
+        error!("failed to run builtin picker: {}", error);
         None
-    } else {
-        Some(id)
-    }
+    })
+}
+
+/// Run the builtin prompt in current terminal.
+fn prompt_in_ui(label: &str, initial: &str) -> Option<String> {
+    ui::prompt(label, initial).unwrap_or_else(|error| {
+        error!("failed to run builtin prompt: {}", error);
+        None
+    })
 }
 
 /// Create new tmux session for dead session.
-fn create_session(name: &str, config: Config) {
+fn create_session(tmux: &dyn TmuxBackend, name: &str, config: Config) -> Result<()> {
     debug!("resurrect session: {}", name);
 
-    let mut path = PathBuf::from(config.sessions_dir);
-    path.push(&format!("{}.tmux-session.zsh", name));
+    let path = config
+        .dead_session
+        .paths
+        .get(name)
+        .ok_or_else(|| TavError::SessionNotFound(name.to_string()))?;
+    debug!("resurrect from {:?}", path);
 
-    let path = path.to_str().unwrap();
-    debug!("run {:?}", path);
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        // create session from definition
+        SessionDef::load(path)?.create(tmux, name)?;
+        return tmux.switch_to(name);
+    }
 
     // create session
-    let output = Command::new(path).output().unwrap();
-    if output.status.success() {
-        tmux::switch_to(name);
+    let output = Command::new(path)
+        .output()
+        .map_err(|e| TavError::io(&path.to_string_lossy(), e))?;
+    if !output.status.success() {
+        return Err(TavError::Script {
+            path: path.to_path_buf(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    tmux.switch_to(name)
+}
+
+/// Create session `name` rooted in its project directory and switch to it.
+fn open_project(tmux: &dyn TmuxBackend, name: &str, config: &Config) -> Result<()> {
+    let dir = config
+        .project
+        .paths
+        .get(name)
+        .ok_or_else(|| TavError::ProjectNotFound(name.to_string()))?;
+    debug!("open project {:?}", dir);
+
+    let id = tmux.new_session(name, dir.to_str())?;
+    tmux.switch_to(&id)
+}
+
+/// List entries of project directory `name`, directories first, headed by
+/// its path.
+fn preview_project(name: &str, config: &Config) -> Result<String> {
+    let dir = match config.project.paths.get(name) {
+        Some(dir) => dir,
+        None => return Ok(String::new()),
+    };
+
+    let entries = fs::read_dir(dir).map_err(|e| TavError::file(dir, e))?;
+    let mut entries: Vec<(bool, String)> = entries
+        .filter_map(|e| {
+            let e = e.ok()?;
+            let is_dir = e.file_type().ok()?.is_dir();
+            Some((!is_dir, e.file_name().to_string_lossy().into_owned()))
+        })
+        .collect();
+    entries.sort();
+
+    let mut s = format!("{}\n\n", style(dir.display()).dim());
+    for (is_file, name) in entries {
+        if is_file {
+            s.push_str(&name);
+        } else {
+            s.push_str(&style(format!("{}/", name)).blue().to_string());
+        }
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+/// Describe what resurrecting dead session `name` will launch: the parsed
+/// definition or the script itself, headed by file path and modification time.
+fn preview_dead_session(name: &str, config: &Config) -> Result<String> {
+    let path = match config.dead_session.paths.get(name) {
+        Some(path) => path,
+        None => return Ok(String::new()),
+    };
+
+    let metadata = fs::metadata(path).map_err(|e| TavError::file(path, e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.elapsed().ok())
+        .map(|elapsed| format!("modified {}", ago(elapsed.as_secs())))
+        .unwrap_or_default();
+
+    let body = if path.extension().is_some_and(|ext| ext == "toml") {
+        SessionDef::load(path)?.summary()
     } else {
-        error!("failed to resurrect session [{}]: {:#?}", name, output);
+        fs::read_to_string(path).map_err(|e| TavError::file(path, e))?
+    };
+
+    Ok(format!(
+        "{}\n{}\n\n{}",
+        style(path.display()).dim(),
+        style(modified).dim(),
+        body
+    ))
+}
+
+/// Format duration of `secs` seconds as `3 days ago`.
+fn ago(secs: u64) -> String {
+    let (n, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86399 => (secs / 3600, "hour"),
+        86400..=2_591_999 => (secs / 86400, "day"),
+        2_592_000..=31_535_999 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    let plural = if n > 1 { "s" } else { "" };
+    format!("{} {}{} ago", n, unit, plural)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::Order;
+    use crate::testing;
+    use crate::tmux::fake::Fake;
+
+    /// `testing::fake` with calling client at %2.
+    fn fake() -> Fake {
+        let tmux = testing::fake();
+        tmux.set_current("%2");
+        tmux
+    }
+
+    fn choice(action: Action, id: &str, query: &str) -> Choice {
+        Choice {
+            action,
+            id: id.to_string(),
+            query: query.to_string(),
+        }
+    }
+
+    fn logged(tmux: &Fake, line: &str) -> bool {
+        tmux.log.borrow().iter().any(|l| l == line)
+    }
+
+    /// Config with session definition `<name>.toml` in a temporary dir.
+    fn config_with_definition(name: &str, definition: &str) -> Config {
+        let dir = testing::temp_dir(name);
+        let path: PathBuf = dir.join(format!("{}.toml", name));
+        fs::write(&path, definition).unwrap();
+
+        let mut config = Config::default();
+        config.dead_session.names.push(name.to_string());
+        config.dead_session.paths.insert(name.to_string(), path);
+        config
+    }
+
+    #[test]
+    fn tav_command_keeps_options() {
+        let mut config = Config::default();
+        config.path = PathBuf::from("/etc/tav.toml");
+        config.servers = vec![
+            Server::Name("work".to_string()),
+            Server::Path("/tmp/it's".to_string()),
+        ];
+        config.backend = Backend::Control;
+        config.git_status = true;
+        config.order = Order::Activity;
+
+        let command = tav_command(&config).unwrap();
+        assert_eq!(
+            command[1..],
+            [
+                "--config",
+                "/etc/tav.toml",
+                "-L",
+                "work",
+                "-S",
+                "/tmp/it's",
+                "--control",
+                "--git",
+                "--order",
+                "activity"
+            ]
+        );
+    }
+
+    #[test]
+    fn switch_to_window_or_pane() {
+        let tmux = fake();
+        let config = Config::default();
+
+        assert!(act(&tmux, &choice(Action::Switch, "@4", ""), &config).unwrap());
+        assert!(logged(&tmux, "switch-client -t @4"));
+
+        switch(&tmux, "%3", &config).unwrap();
+        assert!(logged(&tmux, "switch-client -t %3"));
+        assert!(logged(&tmux, "select-pane -t %3"));
+
+        switch(&tmux, "-Lwork:@1", &config).unwrap();
+        assert!(logged(&tmux, "switch-client -t -Lwork:@1"));
+    }
+
+    #[test]
+    fn kill() {
+        let tmux = fake();
+        let config = Config::default();
+
+        assert!(!act(&tmux, &choice(Action::Kill, "@4", ""), &config).unwrap());
+        assert!(!tmux.window_names(&Server::Default).contains("run"));
+
+        // separator and dead session lines are not killable
+        let before = tmux.log.borrow().len();
+        act(&tmux, &choice(Action::Kill, "[sep]", ""), &config).unwrap();
+        act(&tmux, &choice(Action::Kill, "[dead]x", ""), &config).unwrap();
+        assert_eq!(tmux.log.borrow().len(), before);
+    }
+
+    #[test]
+    fn new_session() {
+        let tmux = fake();
+        let config = Config::default();
+
+        assert!(act(&tmux, &choice(Action::New, "", " gamma "), &config).unwrap());
+        assert!(tmux.session_names(&Server::Default).contains("gamma"));
+        assert!(logged(&tmux, "switch-client -t $9"));
+    }
+
+    #[test]
+    fn new_session_without_name() {
+        let tmux = fake();
+        let config = Config::default();
+
+        assert!(!act(&tmux, &choice(Action::New, "", ""), &config).unwrap());
+        assert!(!act(&tmux, &choice(Action::New, "$0", " \t "), &config).unwrap());
+        assert_eq!(tmux.session_names(&Server::Default).len(), 2);
+        assert!(tmux.log.borrow().is_empty());
+    }
+
+    #[test]
+    fn switch_to_project() {
+        let tmux = fake();
+        let mut config = Config::default();
+        config.project.names.push("tav".to_string());
+        config
+            .project
+            .paths
+            .insert("tav".to_string(), PathBuf::from("/code/tav"));
+
+        assert!(act(&tmux, &choice(Action::Switch, "[project]tav", ""), &config).unwrap());
+        assert!(logged(
+            &tmux,
+            "new-session -d -P -F #{session_id} -s tav -c /code/tav"
+        ));
+        assert!(logged(&tmux, "switch-client -t $9"));
+    }
+
+    #[test]
+    fn switch_to_unknown_project() {
+        let tmux = fake();
+        let config = Config::default();
+
+        let error = switch(&tmux, "[project]gone", &config).unwrap_err();
+        assert!(matches!(error, TavError::ProjectNotFound(name) if name == "gone"));
+        assert!(tmux.log.borrow().is_empty());
+    }
+
+    #[test]
+    fn resurrect_definition() {
+        let tmux = fake();
+        let config = config_with_definition(
+            "proj",
+            r#"
+            root = "/code/proj"
+
+            [[windows]]
+            name = "edit"
+            command = "nvim"
+
+            [[windows]]
+            name = "run"
+            focus = true
+            panes = [{ command = "cargo watch" }, { root = "target" }]
+            "#,
+        );
+
+        switch(&tmux, "[dead]proj", &config).unwrap();
+
+        assert!(tmux.session_names(&Server::Default).contains("proj"));
+        assert!(logged(&tmux, "send-keys -l -t %11 nvim"));
+        assert!(logged(&tmux, "send-keys -t %11 Enter"));
+        assert!(logged(&tmux, "send-keys -l -t %13 cargo watch"));
+        assert!(logged(&tmux, "send-keys -t %13 Enter"));
+        assert!(logged(&tmux, "select-window -t @12"));
+        assert!(logged(&tmux, "switch-client -t proj"));
+
+        let log = tmux.log.borrow();
+        let split = log.iter().find(|l| l.starts_with("split-window")).unwrap();
+        assert!(split.contains("-c /code/proj/target"));
+    }
+
+    #[test]
+    fn resurrect_unknown() {
+        let tmux = fake();
+        let config = Config::default();
+
+        let error = switch(&tmux, "[dead]gone", &config).unwrap_err();
+        assert!(matches!(error, TavError::SessionNotFound(name) if name == "gone"));
+        assert!(tmux.log.borrow().is_empty());
+    }
+
+    #[test]
+    fn preview_lines() {
+        let tmux = fake();
+        let config = config_with_definition(
+            "notes",
+            "[[windows]]\nname = \"journal\"\ncommand = \"nvim\"\n",
+        );
+
+        assert_eq!(preview(&tmux, "@1", &config).unwrap(), "content of @1\n");
+        assert_eq!(preview(&tmux, "[sep]", &config).unwrap(), "");
+
+        let text = preview(&tmux, "[dead]notes", &config).unwrap();
+        let text = console::strip_ansi_codes(&text);
+        assert!(text.contains("notes.toml"));
+        assert!(text.contains("1: journal"));
+        assert!(text.contains("$ nvim"));
+    }
+
+    #[test]
+    fn parse_sigil() {
+        assert_eq!(sigil("$1"), Some('$'));
+        assert_eq!(sigil("-Lwork:@2"), Some('@'));
+        assert_eq!(sigil("-S/tmp/a:b/sock:%3"), Some('%'));
+        assert_eq!(sigil("[dead]x"), Some('['));
+    }
+
+    #[test]
+    fn format_ago() {
+        assert_eq!(ago(5), "just now");
+        assert_eq!(ago(60), "1 minute ago");
+        assert_eq!(ago(3 * 3600), "3 hours ago");
+        assert_eq!(ago(2 * 86400), "2 days ago");
     }
 }
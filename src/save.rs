@@ -0,0 +1,395 @@
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::config::Config;
+use crate::error::{Result, TavError};
+use crate::logging::*;
+use crate::session::{PaneDef, SessionDef, WindowDef};
+use crate::shell::quote;
+use crate::tmux::snapshot::{Pane, Session, Snapshot, Window};
+
+/// Commands not worth restarting in a resurrected pane.
+const SHELLS: [&str; 6] = ["bash", "zsh", "fish", "sh", "dash", "ksh"];
+
+/// Find session by name or id, the current session if `target` is `None`.
+pub fn find_session(snapshot: &Snapshot, target: Option<&str>) -> Result<Rc<RefCell<Session>>> {
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => match &snapshot.current {
+            Some(current) => current.session.clone(),
+            None => return Err(TavError::SessionNotFound("<current>".to_string())),
+        },
+    };
+
+    snapshot
+        .sessions
+        .values()
+        .find(|s| {
+            let s = s.borrow();
+            s.id == target || s.name == target
+        })
+        .cloned()
+        .ok_or(TavError::SessionNotFound(target))
+}
+
+/// Write script (or definition if `toml` is true) which resurrects `session`
+/// into sessions dir, return its path.
+pub fn save(session: &Session, config: &Config, toml: bool, force: bool) -> Result<PathBuf> {
+    // save into the first sessions dir
+    let dir = config.sessions_dirs.first().cloned().unwrap_or_default();
+    fs::create_dir_all(&dir).map_err(|e| TavError::file(&dir, e))?;
+
+    let stem = file_stem(&session.name);
+    let (path, content) = if toml {
+        let path = dir.join(format!("{}.toml", stem));
+        let content = toml::to_string(&definition(session)).map_err(|e| TavError::Definition {
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        (path, content)
+    } else {
+        let path = dir.join(format!("{}.tmux-session.zsh", stem));
+        (path, script(session, &stem))
+    };
+    debug!("save session [{}] to {:?}", session.name, path);
+
+    // only the script is to be run
+    let mut options = OpenOptions::new();
+    options.write(true).mode(if toml { 0o644 } else { 0o755 });
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+
+    let mut file = options.open(&path).map_err(|e| TavError::file(&path, e))?;
+    file.write_all(content.as_bytes())
+        .map_err(|e| TavError::file(&path, e))?;
+
+    Ok(path)
+}
+
+/// File name of session `name` without extension, also the name it is
+/// resurrected as. `/` would point out of sessions dir and is replaced.
+fn file_stem(name: &str) -> String {
+    name.replace(['/', '\0'], "_")
+}
+
+/// Generate session definition which recreates `session`.
+fn definition(session: &Session) -> SessionDef {
+    let mut windows = session
+        .windows
+        .values()
+        .cloned()
+        .collect::<Vec<Rc<RefCell<Window>>>>();
+    windows.sort_by_key(|w| w.borrow().index);
+
+    let windows = windows
+        .iter()
+        .map(|window| {
+            let window = window.borrow();
+
+            let mut panes = window
+                .panes
+                .values()
+                .cloned()
+                .collect::<Vec<Rc<RefCell<Pane>>>>();
+            panes.sort_by_key(|p| p.borrow().index);
+
+            let panes = panes
+                .iter()
+                .map(|pane| {
+                    let pane = pane.borrow();
+                    PaneDef {
+                        root: Some(pane.current_path.clone()),
+                        command: command(&pane),
+                        focus: pane.active && window.panes.len() > 1,
+                    }
+                })
+                .collect();
+
+            WindowDef {
+                name: Some(window.name.clone()),
+                layout: Some(window.layout.clone()),
+                focus: window.active,
+                panes,
+                ..WindowDef::default()
+            }
+        })
+        .collect();
+
+    SessionDef {
+        windows,
+        ..SessionDef::default()
+    }
+}
+
+/// Startup command of a resurrected pane.
+fn command(pane: &Pane) -> Option<String> {
+    if SHELLS.contains(&pane.current_command.as_str()) {
+        None
+    } else {
+        Some(pane.current_command.clone())
+    }
+}
+
+/// Generate zsh script which recreates `session` as `name` in detached mode.
+fn script(session: &Session, name: &str) -> String {
+    let mut windows = session
+        .windows
+        .values()
+        .cloned()
+        .collect::<Vec<Rc<RefCell<Window>>>>();
+    windows.sort_by_key(|w| w.borrow().index);
+
+    let mut s = String::new();
+    let target = quote(&format!("={}", name));
+    let new_window_target = quote(&format!("={}:", name));
+    let name = quote(name);
+
+    s.push_str("#!/usr/bin/env zsh\n");
+    s.push_str("# Generated by `tav save`, edit as you like.\n\n");
+    s.push_str("set -e\n\n");
+    let _ = writeln!(s, "tmux has-session -t {} 2>/dev/null && exit 0\n", target);
+
+    let mut has_active = false;
+
+    for (index, window) in windows.iter().enumerate() {
+        let window = window.borrow();
+
+        let mut panes = window
+            .panes
+            .values()
+            .cloned()
+            .collect::<Vec<Rc<RefCell<Pane>>>>();
+        panes.sort_by_key(|p| p.borrow().index);
+
+        let _ = writeln!(s, "# window {}: {}", window.index, window.name);
+
+        for (pane_index, pane) in panes.iter().enumerate() {
+            let pane = pane.borrow();
+            let path = quote(&pane.current_path);
+
+            if pane_index == 0 {
+                let create = if index == 0 {
+                    format!("new-session -d -s {} -n {}", name, quote(&window.name))
+                } else {
+                    format!(
+                        "new-window -d -t {} -n {}",
+                        new_window_target,
+                        quote(&window.name)
+                    )
+                };
+                let _ = writeln!(s, "w=$(tmux {} -c {} -P -F '#{{window_id}}')", create, path);
+                let _ = writeln!(s, "p=$(tmux display-message -p -t $w '#{{pane_id}}')");
+            } else {
+                let _ = writeln!(
+                    s,
+                    "p=$(tmux split-window -d -t $w -c {} -P -F '#{{pane_id}}')",
+                    path
+                );
+            }
+
+            if let Some(command) = command(&pane) {
+                // `-l` keeps words like `Enter` or `C-c` in command literal
+                let _ = writeln!(s, "tmux send-keys -l -t $p {}", quote(&command));
+                let _ = writeln!(s, "tmux send-keys -t $p Enter");
+            }
+            if pane.active {
+                let _ = writeln!(s, "tmux select-pane -t $p");
+            }
+        }
+
+        let _ = writeln!(s, "tmux select-layout -t $w {}", quote(&window.layout));
+        if window.active {
+            has_active = true;
+            s.push_str("active=$w\n");
+        }
+        s.push('\n');
+    }
+
+    if has_active {
+        s.push_str("tmux select-window -t $active\n");
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    use super::*;
+    use crate::testing::{self, fake};
+    use crate::tmux::fake::Fake;
+    use crate::tmux::server::Server;
+    use crate::tmux::snapshot;
+
+    /// Session alpha of `testing::fake`, with `nvim` running in the second
+    /// pane of edit (%3), which is active, and run (@4) as active window.
+    fn alpha(tmux: &Fake) -> Rc<RefCell<Session>> {
+        let snapshot = snapshot::create(tmux).unwrap();
+        let session = find_session(&snapshot, Some("alpha")).unwrap();
+
+        for window in session.borrow().windows.values() {
+            let mut window = window.borrow_mut();
+            window.active = window.id == "@4";
+            for pane in window.panes.values() {
+                let mut pane = pane.borrow_mut();
+                pane.active = pane.id != "%2";
+                if pane.id == "%3" {
+                    pane.current_command = "nvim".to_string();
+                }
+            }
+        }
+        session
+    }
+
+    fn config(name: &str) -> Config {
+        let mut config = Config::default();
+        config.sessions_dirs = vec![testing::temp_dir(name)];
+        config
+    }
+
+    #[test]
+    fn find_by_name_or_id() {
+        let snapshot = snapshot::create(&fake()).unwrap();
+        assert_eq!(
+            find_session(&snapshot, Some("$6")).unwrap().borrow().name,
+            "beta"
+        );
+        assert_eq!(
+            find_session(&snapshot, Some("beta")).unwrap().borrow().id,
+            "$6"
+        );
+        assert!(matches!(
+            find_session(&snapshot, Some("gone")),
+            Err(TavError::SessionNotFound(_))
+        ));
+        assert!(find_session(&snapshot, None).is_err());
+    }
+
+    #[test]
+    fn script_lines() {
+        let tmux = fake();
+        let script = script(&alpha(&tmux).borrow(), "it's alpha");
+        let lines: Vec<&str> = script.lines().collect();
+
+        assert!(lines.contains(&r#"tmux has-session -t '=it'\''s alpha' 2>/dev/null && exit 0"#));
+        assert!(lines.contains(
+            &r#"w=$(tmux new-session -d -s 'it'\''s alpha' -n 'edit' -c '/home/alpha' -P -F '#{window_id}')"#
+        ));
+        assert!(lines.contains(
+            &r#"w=$(tmux new-window -d -t '=it'\''s alpha:' -n 'run' -c '/home/alpha' -P -F '#{window_id}')"#
+        ));
+
+        // command of the second pane, shells are not restarted
+        let split = lines
+            .iter()
+            .position(|l| l.contains("split-window"))
+            .unwrap();
+        assert_eq!(
+            lines[split + 1..split + 4],
+            [
+                "tmux send-keys -l -t $p 'nvim'",
+                "tmux send-keys -t $p Enter",
+                "tmux select-pane -t $p",
+            ]
+        );
+        assert_eq!(
+            lines.iter().filter(|l| l.contains("send-keys -l")).count(),
+            1
+        );
+
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| **l == "tmux select-layout -t $w 'tiled'")
+                .count(),
+            2
+        );
+        // active window is selected last
+        let active = lines.iter().position(|l| *l == "active=$w").unwrap();
+        assert!(lines[active - 1].contains("select-layout"));
+        assert!(lines[..active].iter().any(|l| l.contains("new-window")));
+        assert_eq!(lines.last(), Some(&"tmux select-window -t $active"));
+    }
+
+    #[test]
+    fn definition_round_trip() {
+        let tmux = fake();
+        let config = config("save-definition");
+        let path = save(&alpha(&tmux).borrow(), &config, true, false).unwrap();
+        assert_eq!(path, config.sessions_dirs[0].join("alpha.toml"));
+
+        let definition = SessionDef::load(&path).unwrap();
+        let names: Vec<Option<&str>> = definition
+            .windows
+            .iter()
+            .map(|w| w.name.as_deref())
+            .collect();
+        assert_eq!(names, [Some("edit"), Some("run")]);
+        assert!(definition.windows[1].focus);
+        assert!(definition.windows[0].panes[1].focus);
+        assert_eq!(
+            definition.windows[0].panes[1].command.as_deref(),
+            Some("nvim")
+        );
+        assert_eq!(definition.windows[0].layout.as_deref(), Some("tiled"));
+
+        // recreate it as another session
+        let target = Fake::new();
+        definition.create(&target, "gamma").unwrap();
+        assert!(target.session_names(&Server::Default).contains("gamma"));
+        assert_eq!(target.window_names(&Server::Default).len(), 2);
+
+        let log = target.log.borrow();
+        let sent: Vec<&String> = log.iter().filter(|l| l.starts_with("send-keys")).collect();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].starts_with("send-keys -l -t %") && sent[0].ends_with(" nvim"));
+        assert!(sent[1].ends_with(" Enter"));
+        assert!(log
+            .iter()
+            .any(|l| l.starts_with("select-layout") && l.ends_with("tiled")));
+    }
+
+    #[test]
+    fn file_modes() {
+        let tmux = fake();
+        let config = config("save-modes");
+        let session = alpha(&tmux);
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode();
+        let script = save(&session.borrow(), &config, false, false).unwrap();
+        assert_ne!(mode(&script) & 0o111, 0);
+        let definition = save(&session.borrow(), &config, true, false).unwrap();
+        assert_eq!(mode(&definition) & 0o111, 0);
+
+        // existing files are kept unless forced
+        assert!(save(&session.borrow(), &config, true, false).is_err());
+        assert!(save(&session.borrow(), &config, true, true).is_ok());
+    }
+
+    #[test]
+    fn file_name_stays_in_dir() {
+        assert_eq!(file_stem("work/api"), "work_api");
+        assert_eq!(file_stem("../up"), ".._up");
+
+        let tmux = fake();
+        let config = config("save-slash");
+        let session = alpha(&tmux);
+        session.borrow_mut().name = "../work/api".to_string();
+
+        let path = save(&session.borrow(), &config, false, false).unwrap();
+        assert_eq!(path.parent(), Some(config.sessions_dirs[0].as_path()));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("-s '.._work_api'"));
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::config::expand;
+use crate::error::{Result, TavError};
+use crate::logging::*;
+use crate::tmux::TmuxBackend;
+
+/// Declarative session definition, loaded from `sessions/<name>.toml`.
+///
+/// ```toml
+/// root = "~/code/tav"
+///
+/// [env]
+/// RUST_LOG = "debug"
+///
+/// [[windows]]
+/// name = "edit"
+/// command = "nvim"
+///
+/// [[windows]]
+/// name = "run"
+/// layout = "even-horizontal"
+/// panes = [
+///   { command = "cargo watch -x check" },
+///   { root = "target", command = "ls" },
+/// ]
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SessionDef {
+    /// Start directory of all windows.
+    pub root: Option<String>,
+
+    /// Environment variables for all windows.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub windows: Vec<WindowDef>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WindowDef {
+    pub name: Option<String>,
+
+    /// Start directory, relative to session root.
+    pub root: Option<String>,
+
+    /// Layout name (e.g. `tiled`) or layout string from `#{window_layout}`.
+    pub layout: Option<String>,
+
+    /// Startup command for window with single pane.
+    pub command: Option<String>,
+
+    /// Make it the current window.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub focus: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub panes: Vec<PaneDef>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PaneDef {
+    /// Start directory, relative to window root.
+    pub root: Option<String>,
+
+    /// Startup command.
+    pub command: Option<String>,
+
+    /// Make it the active pane of its window.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub focus: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl SessionDef {
+    pub fn load(path: &Path) -> Result<SessionDef> {
+        let text = fs::read_to_string(path).map_err(|e| TavError::file(path, e))?;
+        toml::from_str(&text).map_err(|e| TavError::Definition {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Create detached tmux session `name` from this definition.
+    pub fn create(&self, tmux: &dyn TmuxBackend, name: &str) -> Result<()> {
+        debug!("create session [{}] from definition", name);
+
+        let root = self.root.as_deref().map(expand);
+        let env: Vec<String> = self
+            .env
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+
+        let default_window = [WindowDef::default()];
+        let windows = if self.windows.is_empty() {
+            &default_window[..]
+        } else {
+            &self.windows[..]
+        };
+
+        let target = format!("={}:", name);
+        let mut focus = None;
+
+        for (index, window) in windows.iter().enumerate() {
+            let window_root = join(root.as_deref(), window.root.as_deref());
+
+            let single = [PaneDef {
+                command: window.command.clone(),
+                ..PaneDef::default()
+            }];
+            let panes = if window.panes.is_empty() {
+                &single[..]
+            } else {
+                &window.panes[..]
+            };
+
+            let mut window_id = String::new();
+            let mut active_pane = None;
+
+            for (pane_index, pane) in panes.iter().enumerate() {
+                let mut args: Vec<&str> = Vec::new();
+                if pane_index > 0 {
+                    args.extend(&["split-window", "-d", "-t", &window_id]);
+                } else if index > 0 {
+                    args.extend(&["new-window", "-d", "-t", &target]);
+                } else {
+                    args.extend(&["new-session", "-d", "-s", name]);
+                }
+                if pane_index == 0 {
+                    if let Some(name) = &window.name {
+                        args.extend(&["-n", name]);
+                    }
+                }
+
+                let pane_root = join(window_root.as_deref(), pane.root.as_deref());
+                let pane_root = pane_root.as_ref().and_then(|p| p.to_str());
+                if let Some(dir) = pane_root {
+                    args.extend(&["-c", dir]);
+                }
+
+                for e in &env {
+                    args.extend(&["-e", e]);
+                }
+                args.extend(&["-P", "-F", "#{window_id} #{pane_id}"]);
+
+                let output = tmux.output(&args)?;
+                let mut ids = output.split_whitespace();
+                if pane_index == 0 {
+                    window_id = ids.next().unwrap_or_default().to_string();
+                } else {
+                    ids.next();
+                }
+                let pane_id = ids.next().unwrap_or_default().to_string();
+
+                if let Some(command) = &pane.command {
+                    // literally, or words like `Enter` or `C-c` become keys
+                    tmux.output(&["send-keys", "-l", "-t", &pane_id, command])?;
+                    tmux.output(&["send-keys", "-t", &pane_id, "Enter"])?;
+                }
+                if pane.focus {
+                    active_pane = Some(pane_id);
+                }
+            }
+
+            if let Some(layout) = &window.layout {
+                tmux.output(&["select-layout", "-t", &window_id, layout])?;
+            }
+            if let Some(pane_id) = active_pane {
+                tmux.output(&["select-pane", "-t", &pane_id])?;
+            }
+            if window.focus {
+                focus = Some(window_id);
+            }
+        }
+
+        if let Some(window_id) = focus {
+            tmux.output(&["select-window", "-t", &window_id])?;
+        }
+
+        Ok(())
+    }
+
+    /// Human readable outline of windows, panes and their directories and
+    /// commands.
+    pub fn summary(&self) -> String {
+        let mut s = String::new();
+        let root = self.root.as_deref().map(expand);
+
+        if let Some(root) = &root {
+            let _ = writeln!(s, "root: {}", root.display());
+        }
+        for (key, value) in &self.env {
+            let _ = writeln!(s, "env:  {}={}", key, value);
+        }
+
+        for (index, window) in self.windows.iter().enumerate() {
+            let window_root = join(root.as_deref(), window.root.as_deref());
+
+            let _ = write!(
+                s,
+                "\n{} {}",
+                style(format!("{}:", index + 1)).dim(),
+                style(window.name.as_deref().unwrap_or("-")).bold()
+            );
+            if window.focus {
+                s.push_str(" *");
+            }
+            if let Some(layout) = &window.layout {
+                let _ = write!(s, " {}", style(format!("[{}]", layout)).dim());
+            }
+            s.push('\n');
+
+            if window.panes.is_empty() {
+                line(&mut s, window_root.as_deref(), window.command.as_deref());
+            }
+            for pane in &window.panes {
+                let pane_root = join(window_root.as_deref(), pane.root.as_deref());
+                line(&mut s, pane_root.as_deref(), pane.command.as_deref());
+            }
+        }
+
+        s
+    }
+}
+
+/// Append outline line of a pane.
+fn line(s: &mut String, root: Option<&Path>, command: Option<&str>) {
+    let root = root.map(|r| r.display().to_string()).unwrap_or_default();
+    let _ = write!(s, "  {}", style(root).blue());
+    if let Some(command) = command {
+        let _ = write!(s, " {} {}", style("$").dim(), command);
+    }
+    s.push('\n');
+}
+
+/// Resolve `path` against `base`.
+fn join(base: Option<&Path>, path: Option<&str>) -> Option<PathBuf> {
+    match (base, path) {
+        (Some(base), Some(path)) => Some(base.join(expand(path))),
+        (None, Some(path)) => Some(expand(path)),
+        (base, None) => base.map(Path::to_path_buf),
+    }
+}
@@ -0,0 +1,4 @@
+/// Quote `s` for shell.
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r#"'\''"#))
+}
@@ -0,0 +1,25 @@
+//! Fixtures shared by unit tests.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::tmux::fake::Fake;
+use crate::tmux::server::Server;
+
+/// alpha ($0): edit (@1: %2, %3), run (@4: %5); beta ($6): logs (@7: %8)
+pub fn fake() -> Fake {
+    let tmux = Fake::new();
+    tmux.add_pane(&Server::Default, "alpha", "edit", "nvim");
+    tmux.add_pane(&Server::Default, "alpha", "edit", "shell");
+    tmux.add_pane(&Server::Default, "alpha", "run", "cargo");
+    tmux.add_pane(&Server::Default, "beta", "logs", "tail");
+    tmux
+}
+
+/// Empty directory `tav-test-<name>-<pid>` in temp dir, emptied if it exists.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tav-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
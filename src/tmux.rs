@@ -1,2 +1,9 @@
+pub mod backend;
 pub mod cmd;
+pub mod control;
+#[cfg(test)]
+pub mod fake;
+pub mod server;
 pub mod snapshot;
+
+pub use backend::TmuxBackend;
@@ -0,0 +1,139 @@
+use log::debug;
+
+use super::server::Server;
+use crate::error::{Result, TavError};
+
+/// Everything tav asks of tmux.
+///
+/// Targets are session, window or pane ids, possibly qualified with server
+/// (see `Server::qualify`). `cmd::Process` runs the real `tmux` command,
+/// `control::Control` talks to tmux in control mode, tests use an in-memory
+/// fake.
+pub trait TmuxBackend {
+    /// Run tmux with `args` on `server`, return its standard output.
+    fn output_on(&self, server: &Server, args: &[&str]) -> Result<String>;
+
+    /// Switch to session, window or pane `target`, if it is on another server,
+    /// replace the calling client with one attached to that server.
+    ///
+    /// Not run in tmux, replace current process with a client attached to it.
+    fn switch_to(&self, target: &str) -> Result<()>;
+
+    /// Switch to the window containing pane `target`, then make it the active
+    /// pane.
+    fn switch_to_pane(&self, target: &str) -> Result<()>;
+
+    /// Run `command` in a tmux popup window, block until it exits.
+    fn popup(&self, width: usize, height: usize, command: &[&str]) -> Result<()>;
+
+    fn is_in_tmux(&self) -> bool;
+
+    /// Session a client of this backend itself is attached to on `server`,
+    /// which is not to be counted as attached by user.
+    fn own_session(&self, _server: &Server) -> Option<String> {
+        None
+    }
+
+    /// Close connections kept between commands, before other programs talk
+    /// to tmux for the calling client, e.g. `fzf-tmux`.
+    fn disconnect(&self) {}
+
+    /// Run tmux with `args` on default server, return its standard output.
+    fn output(&self, args: &[&str]) -> Result<String> {
+        self.output_on(&Server::Default, args)
+    }
+
+    /// Return lines of `format` for all panes on `server`.
+    fn list(&self, server: &Server, format: &str) -> Result<Vec<String>> {
+        let output = self.output_on(server, &["list-panes", "-a", "-F", format])?;
+        let lines: Vec<&str> = output.split('\n').filter(|x| !x.is_empty()).collect();
+        Ok(lines.into_iter().map(|x| x.to_string()).collect())
+    }
+
+    /// Expand `format` for `target`, or the calling client if `None`.
+    fn display(&self, server: &Server, target: Option<&str>, format: &str) -> Result<String> {
+        let mut args = vec!["display-message", "-p"];
+        if let Some(target) = target {
+            args.extend(&["-t", target]);
+        }
+        args.push(format);
+
+        let output = self.output_on(server, &args)?;
+        Ok(output.trim_end_matches('\n').to_string())
+    }
+
+    /// Return visible content of pane `target` with colors, for a session or
+    /// window, its active pane.
+    fn capture(&self, target: &str) -> Result<String> {
+        let (server, target) = Server::split(target);
+        self.output_on(&server, &["capture-pane", "-e", "-p", "-t", target])
+    }
+
+    /// Kill session, window or pane by its id.
+    fn kill(&self, target: &str) -> Result<()> {
+        debug!("kill: {}", target);
+
+        let (server, target) = Server::split(target);
+        let cmd = match target.chars().next() {
+            Some('$') => "kill-session",
+            Some('@') => "kill-window",
+            _ => "kill-pane",
+        };
+        self.output_on(&server, &[cmd, "-t", target]).map(|_| ())
+    }
+
+    /// Return name of session or window by its id.
+    fn name_of(&self, target: &str) -> Result<String> {
+        let (server, target) = Server::split(target);
+        let format = match target.chars().next() {
+            Some('$') => "#{session_name}",
+            _ => "#{window_name}",
+        };
+        self.display(&server, Some(target), format)
+    }
+
+    /// Rename session or window by its id.
+    fn rename(&self, target: &str, name: &str) -> Result<()> {
+        debug!("rename: {} -> {}", target, name);
+
+        let (server, target) = Server::split(target);
+        let cmd = match target.chars().next() {
+            Some('$') => "rename-session",
+            _ => "rename-window",
+        };
+        self.output_on(&server, &[cmd, "-t", target, name])
+            .map(|_| ())
+    }
+
+    /// Create a detached session, starting in `dir` if given, return its id.
+    fn new_session(&self, name: &str, dir: Option<&str>) -> Result<String> {
+        debug!("new session: {:?} in {:?}", name, dir);
+
+        let mut args = vec!["new-session", "-d", "-P", "-F", "#{session_id}"];
+        if !name.is_empty() {
+            args.extend(&["-s", name]);
+        }
+        if let Some(dir) = dir {
+            args.extend(&["-c", dir]);
+        }
+        Ok(self.output(&args)?.trim().to_string())
+    }
+
+    /// Return tmux client size.
+    fn client_size(&self) -> Result<(usize, usize)> {
+        let output = self.display(&Server::Default, None, "#{client_width}\t#{client_height}")?;
+        let output = output.trim();
+
+        let parse = |token: Option<&str>| -> Result<usize> {
+            token
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| TavError::parse(output, "invalid client size"))
+        };
+
+        let mut tokens = output.split('\t');
+        let width = parse(tokens.next())?;
+        let height = parse(tokens.next())?;
+
+        Ok((width, height))
+    }
+}
@@ -1,38 +1,112 @@
-use std::process::Command;
-use std::str;
+use std::os::unix::process::CommandExt;
 
 use log::debug;
 
-pub fn switch_to(target: &str) {
-    debug!("switch to: {}", target);
+use super::backend::TmuxBackend;
+use super::server::Server;
+use crate::error::{Result, TavError};
+use crate::shell::quote;
 
-    Command::new("tmux")
-        .arg("switch-client")
-        .arg("-t")
-        .arg(target)
-        .spawn()
-        .unwrap();
-}
+/// The real tmux, run as child process.
+pub struct Process;
+
+impl TmuxBackend for Process {
+    fn output_on(&self, server: &Server, args: &[&str]) -> Result<String> {
+        // `-u` keeps tmux from replacing non-ASCII characters and field separator
+        // with `_`, which it does for clients without UTF-8 locale
+        let output = server
+            .command()
+            .arg("-u")
+            .args(args)
+            .output()
+            .map_err(|error| TavError::io("tmux", error))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            if stderr.contains("no server running") || stderr.contains("error connecting to") {
+                return Err(TavError::NoServer);
+            }
+
+            return Err(TavError::Tmux {
+                cmd: format!("tmux {}", args.first().unwrap_or(&"")),
+                stderr,
+            });
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn switch_to(&self, target: &str) -> Result<()> {
+        debug!("switch to: {}", target);
+
+        let (server, target) = Server::split(target);
+        if !self.is_in_tmux() {
+            return attach(&server, target);
+        }
+        if server != Server::Default {
+            return attach_from(&server, target);
+        }
+
+        self.output(&["switch-client", "-t", target]).map(|_| ())
+    }
 
-#[inline]
-pub fn is_in_tmux() -> bool {
-    std::env::var("TMUX").is_ok()
+    fn switch_to_pane(&self, target: &str) -> Result<()> {
+        let (server, pane) = Server::split(target);
+        if server != Server::Default || !self.is_in_tmux() {
+            // select it before attaching, since the attached client shows the
+            // active window
+            debug!("select pane: {}", target);
+            self.output_on(&server, &["select-window", "-t", pane])?;
+            self.output_on(&server, &["select-pane", "-t", pane])?;
+            return self.switch_to(target);
+        }
+
+        self.switch_to(target)?;
+
+        debug!("select pane: {}", target);
+        self.output(&["select-pane", "-t", pane]).map(|_| ())
+    }
+
+    fn popup(&self, width: usize, height: usize, command: &[&str]) -> Result<()> {
+        debug!("popup: {:?}", command);
+
+        let (width, height) = (width.to_string(), height.to_string());
+        let mut args = vec!["display-popup", "-E", "-w", &width, "-h", &height];
+        args.extend(command);
+        self.output(&args).map(|_| ())
+    }
+
+    #[inline]
+    fn is_in_tmux(&self) -> bool {
+        std::env::var("TMUX").is_ok()
+    }
 }
 
-/// Return tmux client size.
-pub fn client_size() -> (usize, usize) {
-    let output = Command::new("tmux")
-        .arg("display-message")
-        .arg("-p")
-        .arg("#{client_width}\t#{client_height}")
-        .output()
-        .unwrap();
+/// Replace current process with a client attached to `target` on `server`,
+/// only returns on failure.
+fn attach(server: &Server, target: &str) -> Result<()> {
+    debug!("exec: tmux attach-session -t {}", target);
+
+    let error = server
+        .command()
+        .arg("attach-session")
+        .arg("-t")
+        .arg(target)
+        .exec();
+    Err(TavError::io("tmux", error))
+}
 
-    let output = str::from_utf8(&output.stdout).unwrap().trim();
-    let mut tokens = output.split("\t");
+/// Detach the calling client and attach to `target` on `server` in its place.
+fn attach_from(server: &Server, target: &str) -> Result<()> {
+    let mut attach = vec!["tmux".to_string()];
+    attach.extend(server.args().iter().map(|a| quote(a)));
+    attach.extend(["attach-session", "-t"].iter().map(|a| a.to_string()));
+    attach.push(quote(target));
+    let attach = attach.join(" ");
 
-    let width: usize = tokens.next().unwrap().parse().unwrap();
-    let height: usize = tokens.next().unwrap().parse().unwrap();
+    debug!("detach and run: {}", attach);
 
-    (width, height)
+    Process
+        .output(&["detach-client", "-E", &attach])
+        .map(|_| ())
 }
@@ -0,0 +1,475 @@
+use std::cell::{OnceCell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+use log::debug;
+
+use super::backend::TmuxBackend;
+use super::cmd::Process;
+use super::server::Server;
+use crate::error::{Result, TavError};
+
+/// Output of a command, or its error message.
+type Reply = std::result::Result<String, String>;
+
+/// tmux in control mode (`tmux -C`), one connection per server, opened on
+/// first command and kept until `disconnect`.
+///
+/// The control client is attached to a session, so tmux may take it for the
+/// calling client: commands about the calling client name it explicitly,
+/// popups and other servers disconnect first.
+///
+/// Every command of the control client counts as activity of its session, it
+/// attaches to the most recently active one to keep the order of sessions,
+/// and is left out of `session_attached` in snapshots.
+#[derive(Default)]
+pub struct Control {
+    connections: RefCell<HashMap<Server, Connection>>,
+    /// Notifications received so far, oldest first.
+    notifications: RefCell<VecDeque<Notification>>,
+    /// Name of calling client on default server, `None` if not run in tmux.
+    client: OnceCell<Option<String>>,
+}
+
+/// Line of tmux outside replies, e.g. `%window-add @3`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// Without leading `%`, e.g. `window-add`.
+    pub name: String,
+    /// Rest of the line, e.g. `@3`.
+    pub args: String,
+}
+
+impl Notification {
+    fn parse(line: &str) -> Option<Notification> {
+        let line = line.strip_prefix('%')?;
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        Some(Notification {
+            name: name.to_string(),
+            args: args.to_string(),
+        })
+    }
+}
+
+struct Connection {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    /// Id of the session the control client is attached to.
+    session: String,
+}
+
+impl Connection {
+    /// Attach a control client to `server`, without output of panes and
+    /// without affecting window sizes.
+    fn open(server: &Server, notifications: &mut VecDeque<Notification>) -> Result<Connection> {
+        debug!("open control connection: {}", server);
+
+        let mut command = server.command();
+        // without `$TMUX` tmux attaches to the most recently active session
+        // instead of the one of calling client, keep talking to its server
+        if let (Server::Default, Ok(tmux)) = (server, env::var("TMUX")) {
+            if let Some(socket) = tmux.split(',').next().filter(|s| !s.is_empty()) {
+                command.args(["-S", socket]);
+            }
+        }
+
+        let mut child = command
+            .env_remove("TMUX")
+            .args(["-u", "-C", "attach-session", "-f", "ignore-size,no-output"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|error| TavError::io("tmux", error))?;
+
+        let mut connection = Connection {
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            session: String::new(),
+        };
+
+        // tmux replies to `attach-session` first, it fails with no sessions
+        match read_reply(&mut connection.stdout, notifications) {
+            Ok(Ok(_)) => {}
+            Ok(Err(message)) => {
+                debug!("control connection failed: {}", message);
+                return Err(TavError::NoServer);
+            }
+            Err(error) => {
+                debug!("control connection failed: {}", error);
+                return Err(TavError::NoServer);
+            }
+        }
+
+        // the control client itself is the client of its commands
+        let session =
+            connection.send(&["display-message", "-p", "#{session_id}"], notifications)?;
+        connection.session = session.trim_end().to_string();
+        debug!("control client attached to: {}", connection.session);
+
+        Ok(connection)
+    }
+
+    fn send(
+        &mut self,
+        args: &[&str],
+        notifications: &mut VecDeque<Notification>,
+    ) -> Result<String> {
+        let line: Vec<String> = args.iter().map(|a| quote(a)).collect();
+        let line = line.join(" ");
+        debug!("control: {}", line);
+
+        let error = |error| TavError::io("tmux -C", error);
+        writeln!(self.stdin, "{}", line).map_err(error)?;
+        self.stdin.flush().map_err(error)?;
+
+        read_reply(&mut self.stdout, notifications)
+            .map_err(error)?
+            .map_err(|stderr| TavError::Tmux {
+                cmd: format!("tmux {}", args.first().unwrap_or(&"")),
+                stderr,
+            })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // an empty line detaches the control client
+        let _ = self.stdin.write_all(b"\n");
+        let _ = self.stdin.flush();
+        let _ = self.child.wait();
+    }
+}
+
+impl Control {
+    /// Take notifications received so far, oldest first.
+    pub fn notifications(&self) -> Vec<Notification> {
+        self.notifications.borrow_mut().drain(..).collect()
+    }
+
+    /// Block until next notification from `server`, return it.
+    pub fn wait(&self, server: &Server) -> Result<Notification> {
+        if let Some(notification) = self.notifications.borrow_mut().pop_front() {
+            return Ok(notification);
+        }
+
+        self.with_connection(server, |connection, _| {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match connection.stdout.read_line(&mut line) {
+                    Ok(0) => return Err(TavError::NoServer),
+                    Ok(_) => {}
+                    Err(error) => return Err(TavError::io("tmux -C", error)),
+                }
+                if let Some(notification) = Notification::parse(line.trim_end_matches('\n')) {
+                    return Ok(notification);
+                }
+            }
+        })
+    }
+
+    /// Name of calling client on default server, found on first use.
+    ///
+    /// Like tmux for commands without `-c`, the most recently active client
+    /// attached to the session of `$TMUX_PANE`, leaving out control clients.
+    fn client(&self) -> Option<&str> {
+        let client = self.client.get_or_init(|| {
+            if !Process.is_in_tmux() {
+                return None;
+            }
+
+            let format = "#{client_activity}\t#{client_control_mode}\t#{client_name}";
+            let pane = env::var("TMUX_PANE").unwrap_or_default();
+            let mut args = vec!["list-clients", "-F", format];
+            if !pane.is_empty() {
+                args.extend(&["-t", &pane]);
+            }
+
+            let client = self
+                .output_on(&Server::Default, &args)
+                .ok()
+                .and_then(|output| best_client(&output));
+            debug!("calling client: {:?}", client);
+            client
+        });
+        client.as_deref()
+    }
+
+    /// Run `f` with connection to `server`, opening it if needed. Drop the
+    /// connection if it is broken, so that next command opens a new one.
+    fn with_connection<T>(
+        &self,
+        server: &Server,
+        f: impl FnOnce(&mut Connection, &mut VecDeque<Notification>) -> Result<T>,
+    ) -> Result<T> {
+        let mut connections = self.connections.borrow_mut();
+        let mut notifications = self.notifications.borrow_mut();
+
+        if !connections.contains_key(server) {
+            let connection = Connection::open(server, &mut notifications)?;
+            connections.insert(server.clone(), connection);
+        }
+
+        let result = f(connections.get_mut(server).unwrap(), &mut notifications);
+        if let Err(TavError::Io { .. }) | Err(TavError::NoServer) = result {
+            connections.remove(server);
+        }
+        result
+    }
+}
+
+impl TmuxBackend for Control {
+    fn output_on(&self, server: &Server, args: &[&str]) -> Result<String> {
+        match self.with_connection(server, |connection, notifications| {
+            connection.send(args, notifications)
+        }) {
+            // not running, `new-session` starts it
+            Err(TavError::NoServer) => Process.output_on(server, args),
+            result => result,
+        }
+    }
+
+    fn display(&self, server: &Server, target: Option<&str>, format: &str) -> Result<String> {
+        let mut args = vec!["display-message", "-p"];
+        match (target, self.client()) {
+            (Some(target), _) => args.extend(&["-t", target]),
+            (None, Some(client)) if *server == Server::Default => args.extend(&["-c", client]),
+            _ => return Process.display(server, target, format),
+        }
+        args.push(format);
+
+        let output = self.output_on(server, &args)?;
+        Ok(output.trim_end_matches('\n').to_string())
+    }
+
+    fn switch_to(&self, target: &str) -> Result<()> {
+        let (server, id) = Server::split(target);
+        match self.client() {
+            Some(client) if server == Server::Default => {
+                debug!("switch to: {}", target);
+                self.output(&["switch-client", "-c", client, "-t", id])
+                    .map(|_| ())
+            }
+            // attaching replaces the calling client or process
+            _ => {
+                self.disconnect();
+                Process.switch_to(target)
+            }
+        }
+    }
+
+    fn switch_to_pane(&self, target: &str) -> Result<()> {
+        let (server, pane) = Server::split(target);
+        match self.client() {
+            Some(_) if server == Server::Default => {
+                self.switch_to(target)?;
+
+                debug!("select pane: {}", target);
+                self.output(&["select-pane", "-t", pane]).map(|_| ())
+            }
+            _ => {
+                self.disconnect();
+                Process.switch_to_pane(target)
+            }
+        }
+    }
+
+    fn popup(&self, width: usize, height: usize, command: &[&str]) -> Result<()> {
+        self.disconnect();
+        Process.popup(width, height, command)
+    }
+
+    fn is_in_tmux(&self) -> bool {
+        Process.is_in_tmux()
+    }
+
+    fn own_session(&self, server: &Server) -> Option<String> {
+        let connections = self.connections.borrow();
+        connections.get(server).map(|c| c.session.clone())
+    }
+
+    fn disconnect(&self) {
+        self.connections.borrow_mut().clear();
+    }
+}
+
+/// Return name of the client with latest activity in lines of `list-clients`
+/// with activity, control mode flag and name, leaving out control clients.
+fn best_client(output: &str) -> Option<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.splitn(3, '\t');
+            let activity: u64 = tokens.next()?.parse().ok()?;
+            match (tokens.next()?, tokens.next()?) {
+                ("0", name) if !name.is_empty() => Some((activity, name)),
+                _ => None,
+            }
+        })
+        .max_by_key(|(activity, _)| *activity)
+        .map(|(_, name)| name.to_string())
+}
+
+/// Read lines up to the end of next `%begin` block, queue notifications
+/// before it.
+fn read_reply(
+    reader: &mut impl BufRead,
+    notifications: &mut VecDeque<Notification>,
+) -> io::Result<Reply> {
+    let mut line = String::new();
+    let mut read_line = |line: &mut String| -> io::Result<()> {
+        line.clear();
+        match reader.read_line(line)? {
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => {
+                let len = line.trim_end_matches('\n').len();
+                line.truncate(len);
+                Ok(())
+            }
+        }
+    };
+
+    // `%begin <time> <number> <flags>`, ended by `%end` or `%error` with the
+    // same arguments, output lines in between are not escaped
+    let guard = loop {
+        read_line(&mut line)?;
+        if let Some(guard) = line.strip_prefix("%begin ") {
+            break guard.to_string();
+        }
+        if let Some(notification) = Notification::parse(&line) {
+            notifications.push_back(notification);
+        }
+    };
+
+    let mut output = String::new();
+    loop {
+        read_line(&mut line)?;
+        match line.split_once(' ') {
+            Some(("%end", rest)) if rest == guard => return Ok(Ok(output)),
+            Some(("%error", rest)) if rest == guard => return Ok(Err(output)),
+            _ => {
+                output.push_str(&line);
+                output.push('\n');
+            }
+        }
+    }
+}
+
+/// Quote `s` for tmux command parser.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\\' | '"' | '$' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply() {
+        let mut notifications = VecDeque::new();
+        let mut input = io::Cursor::new(
+            "%session-changed $0 alpha\n\
+             %begin 1 263 1\n\
+             alpha\x1f%0\n\
+             %end 1 262 1\n\
+             %end 1 263 1\n\
+             %window-renamed @1 new name\n\
+             %begin 1 264 1\n\
+             parse error: unknown command: bogus\n\
+             %error 1 264 1\n\
+             %begin 1 265 1\n\
+             %end 1 265 1\n",
+        );
+
+        let output = read_reply(&mut input, &mut notifications).unwrap();
+        assert_eq!(output, Ok("alpha\x1f%0\n%end 1 262 1\n".to_string()));
+
+        let output = read_reply(&mut input, &mut notifications).unwrap();
+        assert_eq!(
+            output,
+            Err("parse error: unknown command: bogus\n".to_string())
+        );
+
+        let output = read_reply(&mut input, &mut notifications).unwrap();
+        assert_eq!(output, Ok(String::new()));
+
+        let error = read_reply(&mut input, &mut notifications).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn notifications_between_replies() {
+        let mut notifications = VecDeque::new();
+        let mut input = io::Cursor::new(
+            "%begin 1 270 1\n\
+             %end 1 270 1\n\
+             %window-add @3\n\
+             %session-renamed $1 work\n\
+             %begin 1 271 1\n\
+             @3\n\
+             %end 1 271 1\n\
+             %window-add @4\n\
+             %sessions-changed\n\
+             %begin 1 272 1\n\
+             %end 1 272 1\n",
+        );
+        let notification = |name: &str, args: &str| Notification {
+            name: name.to_string(),
+            args: args.to_string(),
+        };
+
+        read_reply(&mut input, &mut notifications).unwrap().unwrap();
+        assert!(notifications.is_empty());
+
+        let output = read_reply(&mut input, &mut notifications).unwrap();
+        assert_eq!(output, Ok("@3\n".to_string()));
+        read_reply(&mut input, &mut notifications).unwrap().unwrap();
+
+        let control = Control::default();
+        control.notifications.replace(notifications);
+        assert_eq!(
+            control.notifications(),
+            [
+                notification("window-add", "@3"),
+                notification("session-renamed", "$1 work"),
+                notification("window-add", "@4"),
+                notification("sessions-changed", ""),
+            ]
+        );
+        assert!(control.notifications().is_empty());
+    }
+
+    #[test]
+    fn calling_client() {
+        let output = "1700000100\t1\tclient-42\n\
+                      1700000050\t0\t/dev/pts/3\n\
+                      1700000080\t0\t/dev/pts/5\n";
+        assert_eq!(best_client(output).as_deref(), Some("/dev/pts/5"));
+        assert_eq!(best_client("1700000100\t1\tclient-42\n"), None);
+        assert_eq!(best_client(""), None);
+    }
+
+    #[test]
+    fn quote_args() {
+        assert_eq!(quote("#{session_name}"), r##""#{session_name}""##);
+        assert_eq!(quote(r#"it's "$HOME" \ ;"#), r#""it's \"\$HOME\" \\ ;""#);
+        assert_eq!(quote("a\nb"), r#""a\nb""#);
+    }
+}
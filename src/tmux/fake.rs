@@ -0,0 +1,352 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+
+use super::backend::TmuxBackend;
+use super::server::Server;
+use crate::error::{Result, TavError};
+
+/// Format variables of a pane, e.g. `session_name`.
+type Vars = HashMap<&'static str, String>;
+
+/// In-memory tmux for tests.
+///
+/// Understands the commands tav runs to list, create, kill and rename, other
+/// commands are only recorded in `log`.
+#[derive(Default)]
+pub struct Fake {
+    /// Panes of each server, in creation order.
+    panes: RefCell<HashMap<Server, Vec<Vars>>>,
+    /// Pane of calling client on default server, `None` if not run in tmux.
+    current: RefCell<Option<String>>,
+    /// Next id number, shared by sessions, windows and panes.
+    serial: Cell<u32>,
+    /// Session the backend's own client is attached to on each server.
+    own_sessions: RefCell<HashMap<Server, String>>,
+    /// Commands run, as tmux command lines.
+    pub log: RefCell<Vec<String>>,
+}
+
+impl Fake {
+    pub fn new() -> Fake {
+        Fake::default()
+    }
+
+    /// Add pane titled `title` into window `window` of session `session`,
+    /// creating them as needed, return pane id.
+    pub fn add_pane(&self, server: &Server, session: &str, window: &str, title: &str) -> String {
+        let mut panes = self.panes.borrow_mut();
+        let panes = panes.entry(server.clone()).or_default();
+
+        let session_id = match panes.iter().find(|p| p["session_name"] == session) {
+            Some(pane) => pane["session_id"].clone(),
+            None => format!("${}", self.next()),
+        };
+        let in_session = |p: &&Vars| p["session_id"] == session_id;
+
+        let (window_id, window_index) = match panes
+            .iter()
+            .filter(in_session)
+            .find(|p| p["window_name"] == window)
+        {
+            Some(pane) => (pane["window_id"].clone(), pane["window_index"].clone()),
+            None => {
+                let windows: BTreeSet<&String> = panes
+                    .iter()
+                    .filter(in_session)
+                    .map(|p| &p["window_id"])
+                    .collect();
+                (format!("@{}", self.next()), windows.len().to_string())
+            }
+        };
+
+        let siblings = panes.iter().filter(|p| p["window_id"] == window_id).count();
+        let first_window = window_index == "0";
+        let serial = self.next().to_string();
+
+        let mut vars = Vars::new();
+        vars.insert("socket_path", format!("/tmp/fake/{}", server));
+        vars.insert("client_width", "200".to_string());
+        vars.insert("client_height", "50".to_string());
+
+        vars.insert("session_id", session_id);
+        vars.insert("session_name", session.to_string());
+        vars.insert("session_attached", "0".to_string());
+        vars.insert("session_activity", serial.clone());
+
+        vars.insert("window_id", window_id);
+        vars.insert("window_index", window_index);
+        vars.insert("window_name", window.to_string());
+        vars.insert("window_active", flag(first_window));
+        vars.insert("window_activity", serial.clone());
+        let flags = if first_window { "*" } else { "" };
+        vars.insert("window_flags", flags.to_string());
+        vars.insert("window_layout", "tiled".to_string());
+
+        let pane_id = format!("%{}", serial);
+        vars.insert("pane_id", pane_id.clone());
+        vars.insert("pane_index", siblings.to_string());
+        vars.insert("pane_title", title.to_string());
+        vars.insert("pane_active", flag(siblings == 0));
+        vars.insert("pane_pid", serial.clone());
+        vars.insert("pane_current_path", format!("/home/{}", session));
+        vars.insert("pane_current_command", "zsh".to_string());
+
+        // adding a pane counts as activity of its window and session
+        for pane in panes.iter_mut() {
+            if pane["session_id"] == vars["session_id"] {
+                pane.insert("session_activity", serial.clone());
+            }
+            if pane["window_id"] == vars["window_id"] {
+                pane.insert("window_activity", serial.clone());
+            }
+        }
+
+        panes.push(vars);
+        pane_id
+    }
+
+    /// Put calling client at pane `id` on default server.
+    pub fn set_current(&self, id: &str) {
+        *self.current.borrow_mut() = Some(id.to_string());
+    }
+
+    /// Names of all sessions on `server`.
+    pub fn session_names(&self, server: &Server) -> BTreeSet<String> {
+        let panes = self.panes.borrow();
+        panes
+            .get(server)
+            .into_iter()
+            .flatten()
+            .map(|p| p["session_name"].clone())
+            .collect()
+    }
+
+    /// Attach a client of the backend itself to session `id` on `server`, as
+    /// a control client does.
+    pub fn attach_own_client(&self, server: &Server, id: &str) {
+        if let Some(panes) = self.panes.borrow_mut().get_mut(server) {
+            for pane in panes.iter_mut().filter(|p| p["session_id"] == id) {
+                let attached: usize = pane["session_attached"].parse().unwrap();
+                pane.insert("session_attached", (attached + 1).to_string());
+            }
+        }
+        let mut own_sessions = self.own_sessions.borrow_mut();
+        own_sessions.insert(server.clone(), id.to_string());
+    }
+
+    /// Names of all windows on `server`.
+    pub fn window_names(&self, server: &Server) -> BTreeSet<String> {
+        let panes = self.panes.borrow();
+        panes
+            .get(server)
+            .into_iter()
+            .flatten()
+            .map(|p| p["window_name"].clone())
+            .collect()
+    }
+
+    fn next(&self) -> u32 {
+        let n = self.serial.get();
+        self.serial.set(n + 1);
+        n
+    }
+
+    /// Find pane by pane id, the active pane of window or session by their id
+    /// or session name.
+    fn resolve(&self, server: &Server, target: &str) -> Option<Vars> {
+        let panes = self.panes.borrow();
+        let panes = panes.get(server)?;
+
+        let target = target.trim_start_matches('=').trim_end_matches(':');
+        let key = match target.chars().next() {
+            Some('%') => "pane_id",
+            Some('@') => "window_id",
+            Some('$') => "session_id",
+            _ => "session_name",
+        };
+
+        let mut matched: Vec<&Vars> = panes.iter().filter(|p| p[key] == target).collect();
+        matched.sort_by_key(|p| (p["window_active"] != "1", p["pane_active"] != "1"));
+        matched.first().map(|p| (*p).clone())
+    }
+
+    fn remove(&self, server: &Server, key: &str, id: &str) {
+        if let Some(panes) = self.panes.borrow_mut().get_mut(server) {
+            panes.retain(|p| p[key] != id);
+        }
+    }
+
+    fn rename(&self, server: &Server, key: &'static str, id_key: &str, id: &str, name: &str) {
+        if let Some(panes) = self.panes.borrow_mut().get_mut(server) {
+            for pane in panes.iter_mut().filter(|p| p[id_key] == id) {
+                pane.insert(key, name.to_string());
+            }
+        }
+    }
+
+    fn is_running(&self, server: &Server) -> bool {
+        self.panes
+            .borrow()
+            .get(server)
+            .is_some_and(|panes| !panes.is_empty())
+    }
+}
+
+impl TmuxBackend for Fake {
+    fn output_on(&self, server: &Server, args: &[&str]) -> Result<String> {
+        let mut line = server.args();
+        line.extend(args);
+        self.log.borrow_mut().push(line.join(" "));
+
+        let option = |name: &str| {
+            args.iter()
+                .position(|a| *a == name)
+                .and_then(|i| args.get(i + 1))
+                .copied()
+        };
+        let print = |vars: &Vars| {
+            if args.contains(&"-P") {
+                format!("{}\n", render(option("-F").unwrap_or(""), vars))
+            } else {
+                String::new()
+            }
+        };
+
+        let creates = ["new-session", "new-window", "split-window"];
+        let command = args.first().copied().unwrap_or("");
+        if !creates.contains(&command) && !self.is_running(server) {
+            return Err(TavError::NoServer);
+        }
+
+        let output = match command {
+            "list-panes" => {
+                let format = option("-F").unwrap_or("");
+                let panes = self.panes.borrow();
+                let lines: Vec<String> = panes[server].iter().map(|p| render(format, p)).collect();
+                lines.join("\n") + "\n"
+            }
+            "display-message" => {
+                // without target, the calling client or the first pane
+                let target = option("-t").map(str::to_string).or_else(|| match server {
+                    Server::Default => self.current.borrow().clone(),
+                    _ => None,
+                });
+                let vars = match target {
+                    Some(target) => self.resolve(server, &target),
+                    None => self.panes.borrow()[server].first().cloned(),
+                };
+                let vars = vars.ok_or_else(|| TavError::Tmux {
+                    cmd: "tmux display-message".to_string(),
+                    stderr: "can't find target".to_string(),
+                })?;
+                format!("{}\n", render(args.last().unwrap(), &vars))
+            }
+            "capture-pane" => format!("content of {}\n", option("-t").unwrap_or("")),
+            "kill-session" => {
+                self.remove(server, "session_id", option("-t").unwrap());
+                String::new()
+            }
+            "kill-window" => {
+                self.remove(server, "window_id", option("-t").unwrap());
+                String::new()
+            }
+            "kill-pane" => {
+                self.remove(server, "pane_id", option("-t").unwrap());
+                String::new()
+            }
+            "rename-session" | "rename-window" => {
+                let (key, id_key) = match command {
+                    "rename-session" => ("session_name", "session_id"),
+                    _ => ("window_name", "window_id"),
+                };
+                let name = args.last().unwrap();
+                self.rename(server, key, id_key, option("-t").unwrap(), name);
+                String::new()
+            }
+            "new-session" => {
+                let name = match option("-s") {
+                    Some(name) => name.to_string(),
+                    None => self.session_names(server).len().to_string(),
+                };
+                let window = option("-n").unwrap_or("zsh");
+                let pane = self.add_pane(server, &name, window, "");
+                print(&self.resolve(server, &pane).unwrap())
+            }
+            "new-window" => {
+                let session = self.resolve(server, option("-t").unwrap()).unwrap();
+                let window = match option("-n") {
+                    Some(name) => name.to_string(),
+                    None => format!("w{}", self.serial.get()),
+                };
+                let pane = self.add_pane(server, &session["session_name"], &window, "");
+                print(&self.resolve(server, &pane).unwrap())
+            }
+            "split-window" => {
+                let window = self.resolve(server, option("-t").unwrap()).unwrap();
+                let pane =
+                    self.add_pane(server, &window["session_name"], &window["window_name"], "");
+                print(&self.resolve(server, &pane).unwrap())
+            }
+            _ => String::new(),
+        };
+
+        Ok(output)
+    }
+
+    fn switch_to(&self, target: &str) -> Result<()> {
+        self.log
+            .borrow_mut()
+            .push(format!("switch-client -t {}", target));
+        Ok(())
+    }
+
+    fn switch_to_pane(&self, target: &str) -> Result<()> {
+        self.switch_to(target)?;
+        self.log
+            .borrow_mut()
+            .push(format!("select-pane -t {}", target));
+        Ok(())
+    }
+
+    fn popup(&self, width: usize, height: usize, command: &[&str]) -> Result<()> {
+        self.log.borrow_mut().push(format!(
+            "display-popup -E -w {} -h {} {}",
+            width,
+            height,
+            command.join(" ")
+        ));
+        Ok(())
+    }
+
+    fn is_in_tmux(&self) -> bool {
+        self.current.borrow().is_some()
+    }
+
+    fn own_session(&self, server: &Server) -> Option<String> {
+        self.own_sessions.borrow().get(server).cloned()
+    }
+}
+
+fn flag(b: bool) -> String {
+    if b { "1" } else { "0" }.to_string()
+}
+
+/// Expand `#{name}` variables in `format`.
+fn render(format: &str, vars: &Vars) -> String {
+    let mut output = String::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find("#{") {
+        output.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &rest[start + 2..end];
+        output.push_str(vars.get(name).map(String::as_str).unwrap_or(""));
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+
+    output
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+/// A tmux server, selected by socket name (`tmux -L`) or socket path
+/// (`tmux -S`).
+///
+/// Ids of sessions, windows and panes on servers other than `Default` are
+/// qualified with the server options, e.g. `-Lwork:@3`, so that they can pass
+/// through fzf as a single field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Server {
+    /// The server plain `tmux` talks to, which is the server of calling client
+    /// if run in tmux.
+    #[default]
+    Default,
+    /// `tmux -L <name>`.
+    Name(String),
+    /// `tmux -S <path>`.
+    Path(String),
+}
+
+impl From<String> for Server {
+    /// `default` (or empty), socket path if it contains `/`, socket name
+    /// otherwise.
+    fn from(s: String) -> Self {
+        if s.is_empty() || s == "default" {
+            Server::Default
+        } else if s.contains('/') {
+            Server::Path(crate::config::expand(&s).to_string_lossy().into_owned())
+        } else {
+            Server::Name(s)
+        }
+    }
+}
+
+impl From<Server> for String {
+    fn from(server: Server) -> Self {
+        server.to_string()
+    }
+}
+
+impl fmt::Display for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Server::Default => write!(f, "default"),
+            Server::Name(name) => write!(f, "{}", name),
+            Server::Path(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+impl Server {
+    /// Options selecting this server on tmux command line.
+    pub fn args(&self) -> Vec<&str> {
+        match self {
+            Server::Default => vec![],
+            Server::Name(name) => vec!["-L", name],
+            Server::Path(path) => vec!["-S", path],
+        }
+    }
+
+    /// `tmux` command talking to this server.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("tmux");
+        cmd.args(self.args());
+        cmd
+    }
+
+    /// Qualify `id` with this server.
+    pub fn qualify(&self, id: &str) -> String {
+        match self {
+            Server::Default => id.to_string(),
+            Server::Name(name) => format!("-L{}:{}", name, id),
+            Server::Path(path) => format!("-S{}:{}", path, id),
+        }
+    }
+
+    /// Split qualified `target` into server and plain tmux id.
+    pub fn split(target: &str) -> (Server, &str) {
+        let parsed = target.rsplit_once(':').and_then(|(server, id)| {
+            if let Some(name) = server.strip_prefix("-L") {
+                Some((Server::Name(name.to_string()), id))
+            } else {
+                server
+                    .strip_prefix("-S")
+                    .map(|path| (Server::Path(path.to_string()), id))
+            }
+        });
+
+        parsed.unwrap_or((Server::Default, target))
+    }
+}
@@ -1,13 +1,24 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::process::Command;
 use std::rc::{Rc, Weak};
-use std::str;
+use std::str::{self, FromStr};
+
+use console::measure_text_width;
+use serde::{Serialize, Serializer};
+
+use super::backend::TmuxBackend;
+use super::server::Server;
+use crate::error::{Result, TavError};
+use crate::logging::*;
 
 type ID = String;
 type Index = u32;
 
-#[derive(Debug)]
+/// Field separator for `tmux list-panes -F`, tmux escapes it in names (as
+/// `\037`) so it never appears in field values.
+const SEP: char = '\x1f';
+
+#[derive(Debug, Default, Serialize)]
 pub struct Counts {
     pub session: usize,
     pub window: usize,
@@ -24,6 +35,7 @@ impl Counts {
     }
 }
 
+/// Display widths in terminal columns.
 #[derive(Debug)]
 pub struct Geometry {
     pub session_name_max_width: usize,
@@ -41,77 +53,299 @@ impl Geometry {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Snapshot {
-    // serverPath: String,
+    pub server: Server,
+    /// Socket path of the server, identifies it regardless of how it was
+    /// selected.
+    pub socket_path: String,
+
+    #[serde(serialize_with = "by_id")]
     pub sessions: HashMap<ID, Rc<RefCell<Session>>>,
     pub counts: Counts,
+    #[serde(skip)]
     pub geometry: Geometry,
+
+    /// Where the calling client is, `None` if not run in tmux.
+    pub current: Option<Current>,
 }
 
-#[derive(Debug)]
+/// Current session, window and pane of the calling client.
+#[derive(Debug, Clone, Serialize)]
+pub struct Current {
+    pub session: ID,
+    pub window: ID,
+    pub pane: ID,
+}
+
+impl Current {
+    #[inline]
+    pub fn is_session(&self, id: &str) -> bool {
+        self.session == id
+    }
+
+    #[inline]
+    pub fn is_window(&self, id: &str) -> bool {
+        self.window == id
+    }
+
+    #[inline]
+    pub fn is_pane(&self, id: &str) -> bool {
+        self.pane == id
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Session {
     pub id: ID,
     pub name: String,
+    pub attached: usize, // number of attached clients
+    pub activity: u64,   // unix time of last activity
 
+    #[serde(serialize_with = "by_id")]
     pub windows: HashMap<ID, Rc<RefCell<Window>>>,
 }
 
-#[derive(Debug)]
+/// Parsed `#{window_flags}`.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct WindowFlags {
+    pub current: bool,  // `*`
+    pub last: bool,     // `-`
+    pub activity: bool, // `#`
+    pub bell: bool,     // `!`
+    pub silence: bool,  // `~`
+    pub marked: bool,   // `M`
+    pub zoomed: bool,   // `Z`
+}
+
+impl WindowFlags {
+    pub fn parse(flags: &str) -> WindowFlags {
+        let mut f = WindowFlags::default();
+        for c in flags.chars() {
+            match c {
+                '*' => f.current = true,
+                '-' => f.last = true,
+                '#' => f.activity = true,
+                '!' => f.bell = true,
+                '~' => f.silence = true,
+                'M' => f.marked = true,
+                'Z' => f.zoomed = true,
+                _ => {}
+            }
+        }
+        f
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Window {
     pub id: ID,
     pub index: Index,
     pub name: String,
+    pub active: bool,  // current window of its session
+    pub activity: u64, // unix time of last activity
+    pub flags: WindowFlags,
+    pub layout: String,
 
+    #[serde(skip)]
     pub session: Weak<RefCell<Session>>,
+    #[serde(serialize_with = "by_id")]
     pub panes: HashMap<ID, Rc<RefCell<Pane>>>,
 }
 
-#[derive(Debug)]
+impl Window {
+    pub fn active_pane(&self) -> Option<Rc<RefCell<Pane>>> {
+        self.panes.values().find(|p| p.borrow().active).cloned()
+    }
+
+    /// Current path of its active pane, empty if unknown.
+    pub fn current_path(&self) -> String {
+        self.active_pane()
+            .map(|p| p.borrow().current_path.clone())
+            .unwrap_or_default()
+    }
+
+    /// Current command of its active pane, empty if unknown.
+    pub fn current_command(&self) -> String {
+        self.active_pane()
+            .map(|p| p.borrow().current_command.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Pane {
     pub id: ID,
     pub index: Index,
     pub title: String,
+    pub active: bool, // current pane of its window
+    pub pid: u32,
+    pub current_path: String,
+    pub current_command: String,
 
+    #[serde(skip)]
     pub window: Weak<RefCell<Window>>,
 }
 
+/// Numeric part of tmux ids like `$1`, `@12` or `%3`.
+pub fn id_number(id: &str) -> u32 {
+    id.trim_start_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .unwrap_or(u32::MAX)
+}
+
+/// Serialize map of sessions, windows or panes as a list ordered by id.
+fn by_id<S, T>(
+    map: &HashMap<ID, Rc<RefCell<T>>>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    let mut items: Vec<(&ID, &Rc<RefCell<T>>)> = map.iter().collect();
+    items.sort_by_key(|(id, _)| id_number(id));
+    serializer.collect_seq(items.into_iter().map(|(_, item)| item))
+}
+
+/// Field cursor over one line of `tmux list-panes` output.
+struct Fields<'a> {
+    line: &'a str,
+    tokens: str::Split<'a, char>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line: &'a str) -> Fields<'a> {
+        Fields {
+            line,
+            tokens: line.split(SEP),
+        }
+    }
+
+    fn next(&mut self, name: &str) -> Result<&'a str> {
+        let line = self.line;
+        self.tokens
+            .next()
+            .ok_or_else(|| TavError::parse(line, format!("missing `{}`", name)))
+    }
+
+    fn parse<T: FromStr>(&mut self, name: &str) -> Result<T> {
+        let token = self.next(name)?;
+        token
+            .parse()
+            .map_err(|_| TavError::parse(self.line, format!("invalid `{}`: {:?}", name, token)))
+    }
+
+    fn flag(&mut self, name: &str) -> Result<bool> {
+        Ok(self.next(name)? == "1")
+    }
+}
+
 /// Run command `tmux list-panes` and collect output lines
-fn list_lines() -> Vec<String> {
+fn list_lines(tmux: &dyn TmuxBackend, server: &Server) -> Result<Vec<String>> {
     let spec = [
         // session
         "#{session_id}",
         "#{session_name}",
+        "#{session_attached}",
+        "#{session_activity}",
         // window
         "#{window_id}",
         "#{window_index}",
         "#{window_name}",
+        "#{window_active}",
+        "#{window_activity}",
+        "#{window_flags}",
+        "#{window_layout}",
         // pane
         "#{pane_id}",
         "#{pane_index}",
         "#{pane_title}",
+        "#{pane_active}",
+        "#{pane_pid}",
+        "#{pane_current_path}",
+        "#{pane_current_command}",
     ]
     .iter()
     .map(|x| x.to_string())
     .collect::<Vec<String>>()
-    .join("\t");
+    .join(&SEP.to_string());
 
-    let output = Command::new("tmux")
-        .arg("list-panes")
-        .arg("-a")
-        .arg("-F")
-        .arg(spec)
-        .output()
-        .expect("failed to run `tmux list-panes`");
+    tmux.list(server, &spec)
+}
 
-    let output = str::from_utf8(&output.stdout).unwrap();
-    let lines: Vec<&str> = output.split('\n').filter(|x| !x.is_empty()).collect();
-    lines.into_iter().map(|x| x.to_string()).collect()
+/// Run command `tmux display-message` to find out where the calling client is.
+fn current(tmux: &dyn TmuxBackend) -> Result<Option<Current>> {
+    if !tmux.is_in_tmux() {
+        return Ok(None);
+    }
+
+    let spec = ["#{session_id}", "#{window_id}", "#{pane_id}"].join(&SEP.to_string());
+    let line = tmux.display(&Server::Default, None, &spec)?;
+    let mut fields = Fields::new(&line);
+    Ok(Some(Current {
+        session: fields.next("session_id")?.to_string(),
+        window: fields.next("window_id")?.to_string(),
+        pane: fields.next("pane_id")?.to_string(),
+    }))
 }
 
 /// Take a snapshot for current tmux client.
-pub fn create() -> Snapshot {
-    let lines = list_lines();
+pub fn create(tmux: &dyn TmuxBackend) -> Result<Snapshot> {
+    create_on(tmux, &Server::Default)
+}
+
+/// Take snapshots of the default server followed by `servers`, servers which
+/// are not running or are already listed are skipped.
+pub fn create_all(tmux: &dyn TmuxBackend, servers: &[Server]) -> Result<Vec<Snapshot>> {
+    // no server is just nothing to list, e.g. run outside tmux
+    let default = match create(tmux) {
+        Err(TavError::NoServer) => empty(Server::Default),
+        result => result?,
+    };
+    let mut snapshots = vec![default];
+
+    for server in servers.iter().filter(|s| **s != Server::Default) {
+        let snapshot = match create_on(tmux, server) {
+            Ok(snapshot) => snapshot,
+            Err(TavError::NoServer) => {
+                debug!("skip server {}: not running", server);
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+
+        if snapshots
+            .iter()
+            .any(|s| s.socket_path == snapshot.socket_path)
+        {
+            debug!("skip server {}: already listed", server);
+            continue;
+        }
+        snapshots.push(snapshot);
+    }
+
+    Ok(snapshots)
+}
+
+/// Snapshot of a server which is not running.
+fn empty(server: Server) -> Snapshot {
+    Snapshot {
+        server,
+        socket_path: String::new(),
+        sessions: HashMap::new(),
+        counts: Counts::new(),
+        geometry: Geometry::new(),
+        current: None,
+    }
+}
+
+/// Take a snapshot of `server`, current location of the calling client is only
+/// known for the default server.
+pub fn create_on(tmux: &dyn TmuxBackend, server: &Server) -> Result<Snapshot> {
+    let lines = list_lines(tmux, server)?;
+    let own_session = tmux.own_session(server);
+    let socket_path = tmux.display(server, None, "#{socket_path}")?;
 
     let mut snw = 0usize; // session name max width
     let mut wnw = 0usize; // window name max width
@@ -120,26 +354,41 @@ pub fn create() -> Snapshot {
     let mut wc = 0usize; // window count
     let pc = lines.len(); // pane count
 
+    let current = match server {
+        Server::Default => current(tmux)?,
+        _ => None,
+    };
+
     let mut tmux = Snapshot {
+        server: server.clone(),
+        socket_path,
         sessions: HashMap::new(),
         counts: Counts::new(),
         geometry: Geometry::new(),
+        current,
     };
 
     for line in lines {
-        let mut tokens = line.split('\t');
+        let mut fields = Fields::new(&line);
 
         //
         // session
         //
 
-        let id = tokens.next().unwrap().to_string();
-        let name = tokens.next().unwrap().to_string();
-        snw = snw.max(name.len());
+        let id = fields.next("session_id")?.to_string();
+        let name = fields.next("session_name")?.to_string();
+        let mut attached: usize = fields.parse("session_attached")?;
+        if own_session.as_ref() == Some(&id) {
+            attached = attached.saturating_sub(1);
+        }
+        let activity: u64 = fields.parse("session_activity")?;
+        snw = snw.max(measure_text_width(&name));
 
         let session = Session {
             id: id.clone(),
             name,
+            attached,
+            activity,
             windows: HashMap::new(),
         };
         let session = Rc::new(RefCell::new(session));
@@ -150,15 +399,23 @@ pub fn create() -> Snapshot {
         // window
         //
 
-        let id = tokens.next().unwrap().to_string();
-        let index: Index = tokens.next().unwrap().parse().unwrap();
-        let name = tokens.next().unwrap().to_string();
-        wnw = wnw.max(name.len());
+        let id = fields.next("window_id")?.to_string();
+        let index: Index = fields.parse("window_index")?;
+        let name = fields.next("window_name")?.to_string();
+        let active = fields.flag("window_active")?;
+        let activity: u64 = fields.parse("window_activity")?;
+        let flags = WindowFlags::parse(fields.next("window_flags")?);
+        let layout = fields.next("window_layout")?.to_string();
+        wnw = wnw.max(measure_text_width(&name));
 
         let window = Window {
             id: id.clone(),
             index,
             name,
+            active,
+            activity,
+            flags,
+            layout,
 
             session: Weak::new(),
             panes: HashMap::new(),
@@ -169,27 +426,35 @@ pub fn create() -> Snapshot {
         }
         let window = session_mut_ref.windows.entry(id).or_insert(window);
         let mut window_mut_ref = window.borrow_mut();
-        window_mut_ref.session = Rc::downgrade(&session);
+        window_mut_ref.session = Rc::downgrade(session);
 
         //
         // pane
         //
 
-        let id = tokens.next().unwrap().to_string();
-        let index: Index = tokens.next().unwrap().parse().unwrap();
-        let title = tokens.next().unwrap().to_string();
-        ptw = ptw.max(title.len());
+        let id = fields.next("pane_id")?.to_string();
+        let index: Index = fields.parse("pane_index")?;
+        let title = fields.next("pane_title")?.to_string();
+        let active = fields.flag("pane_active")?;
+        let pid: u32 = fields.parse("pane_pid")?;
+        let current_path = fields.next("pane_current_path")?.to_string();
+        let current_command = fields.next("pane_current_command")?.to_string();
+        ptw = ptw.max(measure_text_width(&title));
 
         let pane = Pane {
             id: id.clone(),
             index,
             title,
+            active,
+            pid,
+            current_path,
+            current_command,
             window: Weak::new(),
         };
 
         let pane = Rc::new(RefCell::new(pane));
         let pane = window_mut_ref.panes.entry(id).or_insert(pane);
-        pane.borrow_mut().window = Rc::downgrade(&window);
+        pane.borrow_mut().window = Rc::downgrade(window);
     }
 
     // geometry
@@ -202,5 +467,156 @@ pub fn create() -> Snapshot {
     tmux.counts.window = wc;
     tmux.counts.pane = pc;
 
-    tmux
+    Ok(tmux)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fake;
+    use crate::tmux::fake::Fake;
+
+    fn session(snapshot: &Snapshot, name: &str) -> Rc<RefCell<Session>> {
+        snapshot
+            .sessions
+            .values()
+            .find(|s| s.borrow().name == name)
+            .cloned()
+            .unwrap()
+    }
+
+    #[test]
+    fn tree() {
+        let snapshot = create(&fake()).unwrap();
+
+        assert_eq!(snapshot.counts.session, 2);
+        assert_eq!(snapshot.counts.window, 3);
+        assert_eq!(snapshot.counts.pane, 4);
+
+        let alpha = session(&snapshot, "alpha");
+        let alpha = alpha.borrow();
+        assert_eq!(alpha.windows.len(), 2);
+
+        let edit = alpha.windows.values().find(|w| w.borrow().index == 0);
+        let edit = edit.unwrap().borrow();
+        assert_eq!(edit.name, "edit");
+        assert!(edit.active);
+        assert!(edit.flags.current);
+        assert_eq!(edit.session.upgrade().unwrap().borrow().name, "alpha");
+
+        let mut titles: Vec<String> = edit
+            .panes
+            .values()
+            .map(|p| p.borrow().title.clone())
+            .collect();
+        titles.sort();
+        assert_eq!(titles, ["nvim", "shell"]);
+
+        for pane in edit.panes.values() {
+            let pane = pane.borrow();
+            assert_eq!(pane.window.upgrade().unwrap().borrow().id, edit.id);
+            assert_eq!(pane.current_path, "/home/alpha");
+            assert_eq!(pane.active, pane.index == 0);
+        }
+    }
+
+    #[test]
+    fn geometry() {
+        let snapshot = create(&fake()).unwrap();
+
+        assert_eq!(snapshot.geometry.session_name_max_width, "alpha".len());
+        assert_eq!(snapshot.geometry.window_name_max_width, "edit".len());
+        assert_eq!(snapshot.geometry.pane_title_max_width, "cargo".len());
+
+        // display width, not bytes
+        let tmux = fake();
+        tmux.add_pane(&Server::Default, "日本語のセッション", "編集", "🦀");
+        let snapshot = create(&tmux).unwrap();
+        assert_eq!(snapshot.geometry.session_name_max_width, 18);
+        assert_eq!(snapshot.geometry.window_name_max_width, "edit".len());
+    }
+
+    #[test]
+    fn own_client_not_attached() {
+        let tmux = fake();
+        tmux.attach_own_client(&Server::Default, "$6");
+        let snapshot = create(&tmux).unwrap();
+
+        assert_eq!(session(&snapshot, "alpha").borrow().attached, 0);
+        assert_eq!(session(&snapshot, "beta").borrow().attached, 0);
+
+        tmux.attach_own_client(&Server::Default, "$0");
+        tmux.attach_own_client(&Server::Default, "$0");
+        let snapshot = create(&tmux).unwrap();
+        // only the latest attachment is the own client, earlier ones count
+        // as by user
+        assert_eq!(session(&snapshot, "alpha").borrow().attached, 1);
+        assert_eq!(session(&snapshot, "beta").borrow().attached, 1);
+    }
+
+    #[test]
+    fn current() {
+        let tmux = fake();
+        assert!(create(&tmux).unwrap().current.is_none());
+
+        let pane = tmux.add_pane(&Server::Default, "alpha", "run", "test");
+        tmux.set_current(&pane);
+
+        let snapshot = create(&tmux).unwrap();
+        let current = snapshot.current.as_ref().unwrap();
+        let alpha = session(&snapshot, "alpha");
+        assert!(current.is_session(&alpha.borrow().id));
+        assert!(current.is_pane(&pane));
+
+        let alpha = alpha.borrow();
+        let run = alpha.windows.values().find(|w| w.borrow().name == "run");
+        assert!(current.is_window(&run.unwrap().borrow().id));
+    }
+
+    #[test]
+    fn no_server() {
+        let tmux = Fake::new();
+        assert!(matches!(create(&tmux), Err(TavError::NoServer)));
+
+        let snapshots = create_all(&tmux, &[]).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert!(snapshots[0].sessions.is_empty());
+    }
+
+    #[test]
+    fn other_servers() {
+        let tmux = fake();
+        let work = Server::Name("work".to_string());
+        tmux.add_pane(&work, "work", "logs", "tail");
+        tmux.set_current("%2");
+
+        let servers = [work.clone(), Server::Name("gone".to_string())];
+        let snapshots = create_all(&tmux, &servers).unwrap();
+
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].server, Server::Default);
+        assert_eq!(snapshots[1].server, work);
+        assert_eq!(snapshots[1].socket_path, "/tmp/fake/work");
+        assert!(snapshots[0].current.is_some());
+        assert!(snapshots[1].current.is_none());
+    }
+
+    #[test]
+    fn window_flags() {
+        let flags = WindowFlags::parse("*Z");
+        assert!(flags.current && flags.zoomed);
+        assert!(!flags.last && !flags.bell);
+
+        let flags = WindowFlags::parse("-#!");
+        assert!(flags.last && flags.activity && flags.bell);
+        assert!(!flags.current);
+    }
+
+    #[test]
+    fn parse_id_number() {
+        assert_eq!(id_number("$1"), 1);
+        assert_eq!(id_number("@12"), 12);
+        assert_eq!(id_number("%3"), 3);
+        assert_eq!(id_number("[dead]x"), u32::MAX);
+    }
 }
@@ -0,0 +1,535 @@
+use std::io::{self, Write};
+
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use tui::backend::TermionBackend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{List, ListItem, ListState, Paragraph};
+use tui::Terminal;
+
+use crate::action::{Action, Choice};
+use crate::config::{Config, Keys, Theme};
+use crate::logging::*;
+
+/// One line of the fzf feed, prepared for rendering and matching.
+struct Row {
+    id: String,
+    text: String,
+    spans: Spans<'static>,
+}
+
+impl Row {
+    fn new(line: &str) -> Row {
+        let (id, body) = line.split_once('\t').unwrap_or((line, ""));
+
+        Row {
+            id: id.to_string(),
+            text: console::strip_ansi_codes(body).to_string(),
+            spans: ansi_spans(body),
+        }
+    }
+
+    #[inline]
+    fn is_separator(&self) -> bool {
+        self.id == "[sep]"
+    }
+}
+
+struct Picker {
+    rows: Vec<Row>,
+    query: String,
+    matches: Vec<usize>, // indices into `rows`
+    state: ListState,
+}
+
+impl Picker {
+    fn new(feed: &[String], initial: Option<usize>) -> Picker {
+        let mut p = Picker {
+            rows: feed.iter().map(|line| Row::new(line)).collect(),
+            query: String::new(),
+            matches: Vec::new(),
+            state: ListState::default(),
+        };
+
+        p.filter();
+        if let Some(index) = initial.filter(|&i| i < p.matches.len()) {
+            p.state.select(Some(index));
+        }
+        p
+    }
+
+    /// Recompute matched rows for current query, keep cursor on the first
+    /// selectable row.
+    fn filter(&mut self) {
+        let query = &self.query;
+        self.matches = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| {
+                if query.is_empty() {
+                    true
+                } else {
+                    !row.is_separator() && fuzzy_match(query, &row.text)
+                }
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        self.state.select(None);
+        self.move_cursor(1);
+    }
+
+    /// Move cursor by `offset` lines, skipping separator lines.
+    fn move_cursor(&mut self, offset: isize) {
+        let count = self.matches.len() as isize;
+        let target = match self.state.selected() {
+            Some(index) => (index as isize + offset).min(count - 1).max(0),
+            None => 0,
+        };
+
+        // look for selectable line in moving direction first, then backwards
+        let step = if offset < 0 { -1 } else { 1 };
+        let found = self
+            .find_selectable(target, step)
+            .or_else(|| self.find_selectable(target, -step));
+        self.state.select(found);
+    }
+
+    fn find_selectable(&self, from: isize, step: isize) -> Option<usize> {
+        let mut index = from;
+        while (0..self.matches.len() as isize).contains(&index) {
+            if !self.row_at(index as usize).is_separator() {
+                return Some(index as usize);
+            }
+            index += step;
+        }
+        None
+    }
+
+    fn row_at(&self, index: usize) -> &Row {
+        &self.rows[self.matches[index]]
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        let index = self.state.selected()?;
+        Some(self.row_at(index).id.clone())
+    }
+
+    fn choice(&self, action: Action) -> Option<Choice> {
+        let id = self.selected_id().unwrap_or_default();
+        if id.is_empty() && action != Action::New {
+            return None;
+        }
+
+        let choice = Choice {
+            action,
+            id,
+            query: self.query.clone(),
+        };
+        debug!("choice: {:?}", choice);
+        Some(choice)
+    }
+}
+
+/// Run the native picker in current terminal, return the chosen line and
+/// action.
+///
+/// Cursor starts on line `initial` if given.
+pub fn choose(
+    feed: &[String],
+    initial: Option<usize>,
+    config: &Config,
+) -> io::Result<Option<Choice>> {
+    let tty = termion::get_tty()?;
+    let keys = tty.try_clone()?.keys();
+    let output = AlternateScreen::from(tty.into_raw_mode()?);
+
+    let mut terminal = Terminal::new(TermionBackend::new(output))?;
+    let mut picker = Picker::new(feed, initial);
+
+    draw(&mut terminal, &mut picker, &config.theme)?;
+
+    for key in keys {
+        let key = key?;
+        if let Some(action) = action_of(&key, &config.keys) {
+            return Ok(picker.choice(action));
+        }
+
+        match key {
+            Key::Esc | Key::Ctrl('c') | Key::Ctrl('g') | Key::Ctrl('q') => return Ok(None),
+            // termion reads Enter, Ctrl-J and Ctrl-M all as `\n`
+            Key::Char('\n') => return Ok(picker.choice(Action::Switch)),
+
+            Key::Up | Key::Ctrl('p') | Key::Ctrl('k') => picker.move_cursor(-1),
+            Key::Down | Key::Ctrl('n') => picker.move_cursor(1),
+            Key::PageUp | Key::Ctrl('b') => picker.move_cursor(-page_size(&terminal)),
+            Key::PageDown | Key::Ctrl('f') => picker.move_cursor(page_size(&terminal)),
+
+            Key::Backspace | Key::Ctrl('h') => {
+                picker.query.pop();
+                picker.filter();
+            }
+            Key::Ctrl('u') => {
+                picker.query.clear();
+                picker.filter();
+            }
+            Key::Char(c) if !c.is_control() => {
+                picker.query.push(c);
+                picker.filter();
+            }
+
+            _ => continue,
+        }
+
+        draw(&mut terminal, &mut picker, &config.theme)?;
+    }
+
+    Ok(None)
+}
+
+/// Run a one line input box in current terminal, return the input, `None` if
+/// canceled.
+pub fn prompt(label: &str, initial: &str) -> io::Result<Option<String>> {
+    let tty = termion::get_tty()?;
+    let keys = tty.try_clone()?.keys();
+    let output = AlternateScreen::from(tty.into_raw_mode()?);
+
+    let mut terminal = Terminal::new(TermionBackend::new(output))?;
+    let mut input = initial.to_string();
+
+    let draw = |terminal: &mut Terminal<_>, input: &str| -> io::Result<()> {
+        terminal.draw(|f| {
+            let area = Layout::default()
+                .horizontal_margin(2)
+                .vertical_margin(1)
+                .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                .split(f.size())[0];
+
+            let line = Spans::from(vec![
+                Span::styled(label.to_string(), Style::default().fg(Color::Blue)),
+                Span::raw(input.to_string()),
+            ]);
+            f.render_widget(Paragraph::new(line), area);
+
+            let x = console::measure_text_width(label) + console::measure_text_width(input);
+            f.set_cursor(area.x + x as u16, area.y);
+        })?;
+        Ok(())
+    };
+
+    draw(&mut terminal, &input)?;
+
+    for key in keys {
+        match key? {
+            Key::Esc | Key::Ctrl('c') | Key::Ctrl('g') | Key::Ctrl('q') => return Ok(None),
+            Key::Char('\n') => return Ok(Some(input)),
+            Key::Backspace | Key::Ctrl('h') => {
+                input.pop();
+            }
+            Key::Ctrl('u') => input.clear(),
+            Key::Char(c) if !c.is_control() => input.push(c),
+            _ => continue,
+        }
+
+        draw(&mut terminal, &input)?;
+    }
+
+    Ok(None)
+}
+
+/// Action bound to `key` in `[keys]` config.
+fn action_of(key: &Key, keys: &Keys) -> Option<Action> {
+    let name = match key {
+        Key::Ctrl(c) => format!("ctrl-{}", c),
+        Key::Alt(c) => format!("alt-{}", c),
+        _ => return None,
+    };
+
+    match Action::from_key(&name, keys) {
+        Action::Switch => None,
+        action => Some(action),
+    }
+}
+
+fn page_size<B: tui::backend::Backend>(terminal: &Terminal<B>) -> isize {
+    let height = terminal.size().map(|r| r.height).unwrap_or(0) as isize;
+    (height - 4).max(1)
+}
+
+fn draw<W: Write>(
+    terminal: &mut Terminal<TermionBackend<W>>,
+    picker: &mut Picker,
+    theme: &Theme,
+) -> io::Result<()> {
+    let pointer = format!("{} ", theme.pointer);
+
+    terminal.draw(|f| {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .horizontal_margin(2)
+            .vertical_margin(1)
+            .constraints(
+                [
+                    Constraint::Length(1), // prompt
+                    Constraint::Length(1), // info
+                    Constraint::Min(0),    // list
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+
+        // prompt
+
+        let prompt = Spans::from(vec![
+            Span::styled(theme.prompt.clone(), Style::default().fg(Color::Blue)),
+            Span::raw(picker.query.clone()),
+        ]);
+        f.render_widget(Paragraph::new(prompt), chunks[0]);
+
+        let x = chunks[0].x + console::measure_text_width(&theme.prompt) as u16;
+        let x = x + console::measure_text_width(&picker.query) as u16;
+        f.set_cursor(x, chunks[0].y);
+
+        // info
+
+        let total = picker.rows.iter().filter(|r| !r.is_separator()).count();
+        let matched = picker
+            .matches
+            .iter()
+            .filter(|&&i| !picker.rows[i].is_separator())
+            .count();
+        let info = Span::styled(
+            format!("{}/{}", matched, total),
+            Style::default().fg(Color::Indexed(242)),
+        );
+        f.render_widget(Paragraph::new(info), chunks[1]);
+
+        // list
+
+        let items: Vec<ListItem> = picker
+            .matches
+            .iter()
+            .map(|&i| ListItem::new(picker.rows[i].spans.clone()))
+            .collect();
+
+        let list = List::new(items)
+            .highlight_symbol(&pointer)
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+        f.render_stateful_widget(list, chunks[2], &mut picker.state);
+    })?;
+
+    Ok(())
+}
+
+/// Smart-case subsequence match, all space separated terms must match.
+fn fuzzy_match(query: &str, text: &str) -> bool {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let text: String = if ignore_case {
+        text.to_lowercase()
+    } else {
+        text.to_string()
+    };
+
+    query.split_whitespace().all(|term| {
+        let mut chars = text.chars();
+        term.chars().all(|qc| chars.any(|tc| tc == qc))
+    })
+}
+
+/// Convert SGR escape sequences emitted by `console` into styled spans.
+fn ansi_spans(line: &str) -> Spans<'static> {
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    let mut rest = line;
+
+    while let Some(start) = rest.find("\u{1b}[") {
+        if start > 0 {
+            spans.push(Span::styled(rest[..start].to_string(), style));
+        }
+        rest = &rest[start + 2..];
+
+        let end = match rest.find('m') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let mut codes = rest[..end].split(';').map(|c| c.parse::<u8>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            style = match code {
+                0 => Style::default(),
+                1 => style.add_modifier(Modifier::BOLD),
+                2 => style.add_modifier(Modifier::DIM),
+                3 => style.add_modifier(Modifier::ITALIC),
+                4 => style.add_modifier(Modifier::UNDERLINED),
+                7 => style.add_modifier(Modifier::REVERSED),
+                30..=37 => style.fg(Color::Indexed(code - 30)),
+                90..=97 => style.fg(Color::Indexed(code - 90 + 8)),
+                38 => match extended_color(&mut codes) {
+                    Some(color) => style.fg(color),
+                    None => style,
+                },
+                39 => style.fg(Color::Reset),
+                40..=47 => style.bg(Color::Indexed(code - 40)),
+                48 => match extended_color(&mut codes) {
+                    Some(color) => style.bg(color),
+                    None => style,
+                },
+                49 => style.bg(Color::Reset),
+                _ => style,
+            };
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    if !rest.is_empty() {
+        spans.push(Span::styled(rest.to_string(), style));
+    }
+
+    Spans::from(spans)
+}
+
+/// Color of `5;n` (256 colors) or `2;r;g;b` (truecolor) following SGR code
+/// `38` or `48`.
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Indexed(codes.next()?)),
+        2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker(ids: &[&str]) -> Picker {
+        let feed: Vec<String> = ids.iter().map(|id| format!("{}\t{}", id, id)).collect();
+        Picker::new(&feed, None)
+    }
+
+    #[test]
+    fn action_keys() {
+        let keys = Keys::default();
+        assert_eq!(action_of(&Key::Ctrl('x'), &keys), Some(Action::Kill));
+        assert_eq!(action_of(&Key::Ctrl('r'), &keys), Some(Action::Rename));
+        assert_eq!(action_of(&Key::Alt('n'), &keys), Some(Action::New));
+        assert_eq!(action_of(&Key::Ctrl('n'), &keys), None);
+        assert_eq!(action_of(&Key::Char('x'), &keys), None);
+
+        let keys = Keys {
+            kill: "alt-k".to_string(),
+            ..Keys::default()
+        };
+        assert_eq!(action_of(&Key::Alt('k'), &keys), Some(Action::Kill));
+        assert_eq!(action_of(&Key::Ctrl('x'), &keys), None);
+    }
+
+    #[test]
+    fn sgr_spans() {
+        let spans = ansi_spans("\u{1b}[35mmain\u{1b}[0m: \u{1b}[1;38;5;242m~/src\u{1b}[0m");
+        let styled: Vec<(&str, Style)> = spans
+            .0
+            .iter()
+            .map(|span| (span.content.as_ref(), span.style))
+            .collect();
+        assert_eq!(
+            styled,
+            vec![
+                ("main", Style::default().fg(Color::Indexed(5))),
+                (": ", Style::default()),
+                (
+                    "~/src",
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .fg(Color::Indexed(242))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn sgr_truecolor() {
+        let spans = ansi_spans("\u{1b}[38;2;255;128;0;48;2;0;0;64mfire\u{1b}[39;49m!");
+        assert_eq!(spans.0[0].content, "fire");
+        assert_eq!(
+            spans.0[0].style,
+            Style::default()
+                .fg(Color::Rgb(255, 128, 0))
+                .bg(Color::Rgb(0, 0, 64))
+        );
+        assert_eq!(
+            spans.0[1].style,
+            Style::default().fg(Color::Reset).bg(Color::Reset)
+        );
+    }
+
+    #[test]
+    fn sgr_incomplete() {
+        // truncated color is ignored
+        let spans = ansi_spans("\u{1b}[38;2;1mtext");
+        assert_eq!(spans.0.len(), 1);
+        assert_eq!(spans.0[0].content, "text");
+        assert_eq!(spans.0[0].style, Style::default());
+    }
+
+    #[test]
+    fn cursor_clamps() {
+        let mut p = picker(&["$1", "@1", "@2"]);
+        assert_eq!(p.state.selected(), Some(0));
+
+        p.move_cursor(-1);
+        assert_eq!(p.state.selected(), Some(0));
+        p.move_cursor(10);
+        assert_eq!(p.state.selected(), Some(2));
+        p.move_cursor(1);
+        assert_eq!(p.state.selected(), Some(2));
+        p.move_cursor(-10);
+        assert_eq!(p.state.selected(), Some(0));
+    }
+
+    #[test]
+    fn cursor_skips_separators() {
+        let mut p = picker(&["[sep]", "$1", "[sep]", "$2", "[sep]"]);
+        assert_eq!(p.state.selected(), Some(1));
+
+        p.move_cursor(1);
+        assert_eq!(p.state.selected(), Some(3));
+        // nothing selectable below, stays on last selectable line
+        p.move_cursor(1);
+        assert_eq!(p.state.selected(), Some(3));
+        p.move_cursor(-1);
+        assert_eq!(p.state.selected(), Some(1));
+        p.move_cursor(-1);
+        assert_eq!(p.state.selected(), Some(1));
+    }
+
+    #[test]
+    fn cursor_after_filter() {
+        let mut p = picker(&["[sep]", "alpha", "beta", "gamma"]);
+        p.query = "a".to_string();
+        p.filter();
+        assert_eq!(p.matches, vec![1, 2, 3]);
+        assert_eq!(p.selected_id().as_deref(), Some("alpha"));
+
+        p.query = "zzz".to_string();
+        p.filter();
+        assert!(p.matches.is_empty());
+        assert_eq!(p.state.selected(), None);
+        p.move_cursor(1);
+        assert_eq!(p.state.selected(), None);
+        assert!(p.choice(Action::Switch).is_none());
+    }
+
+    #[test]
+    fn initial_cursor() {
+        let feed = vec!["$1\ta".to_string(), "$2\tb".to_string()];
+        assert_eq!(Picker::new(&feed, Some(1)).state.selected(), Some(1));
+        assert_eq!(Picker::new(&feed, Some(5)).state.selected(), Some(0));
+    }
+}